@@ -0,0 +1,65 @@
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use rlox::Token;
+
+// Sizes of the generated scripts. A linear scanner takes about four times
+// as long on the larger one, a quadratic one about sixteen.
+const SMALL_BYTES: usize = 1024 * 1024;
+const LARGE_BYTES: usize = 4 * 1024 * 1024;
+const RUNS: u32 = 5;
+
+// A script heavy on what the scanner has to walk through: comments and
+// string literals with non-ASCII text, plus ordinary tokens in between.
+fn generate_source(target_bytes: usize) -> String {
+    let mut source: String = String::with_capacity(target_bytes + 1024);
+    let mut i: usize = 0;
+    while source.len() < target_bytes {
+        source.push_str("// Grüße, ünïcödé and ✓ marks in a comment line\n");
+        source.push_str(&format!(
+            "var s{} = \"naïve café {} — 日本語のテキスト\";\n",
+            i, i
+        ));
+        source.push_str(&format!("var n{} = ({} + 2.5) * 3 >= 10 or false;\n", i, i));
+        i += 1;
+    }
+    source.push_str("print \"done\";\n");
+    source
+}
+
+// Best of RUNS at scanning the whole source, with the token count
+fn time_scan(source: &str) -> (Duration, usize) {
+    let mut best: Duration = Duration::MAX;
+    let mut count: usize = 0;
+    for _ in 0..RUNS {
+        let start: Instant = Instant::now();
+        let tokens: Vec<Token> = rlox::scan(black_box(source)).unwrap();
+        best = best.min(start.elapsed());
+        count = tokens.len();
+    }
+    (best, count)
+}
+
+fn main() {
+    let mut timings: Vec<Duration> = Vec::new();
+    for target_bytes in [SMALL_BYTES, LARGE_BYTES] {
+        let source: String = generate_source(target_bytes);
+        let (best, tokens) = time_scan(&source);
+        let megabytes: f64 = source.len() as f64 / (1024.0 * 1024.0);
+        println!(
+            "scanner: {:.1} MB, {} tokens in {:?} (best of {}), {:.1} MB/s",
+            megabytes,
+            tokens,
+            best,
+            RUNS,
+            megabytes / best.as_secs_f64()
+        );
+        timings.push(best);
+    }
+    println!(
+        "scanner: 4x the input took {:.1}x as long",
+        timings[1].as_secs_f64() / timings[0].as_secs_f64()
+    );
+}
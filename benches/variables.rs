@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    hint::black_box,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use rlox::Symbol;
+
+const RUNS: u32 = 5;
+
+// Dominated by variable lookups: globals, locals several scopes up, closure
+// captures and instance fields, with little else going on.
+const SCRIPT: &str = r#"
+var total = 0;
+var step = 1;
+
+class Counter {
+    init() {
+        this.count = 0;
+        this.limit = 3;
+    }
+
+    bump() {
+        this.count = this.count + step;
+        if (this.count > this.limit) this.count = 0;
+        return this.count;
+    }
+}
+
+fun makeAdder(offset) {
+    var base = offset * 2;
+    fun add(value) {
+        return value + base + offset;
+    }
+    return add;
+}
+
+var counter = Counter();
+var add = makeAdder(step);
+for (var i = 0; i < 200000; i = i + 1) {
+    var a = i;
+    {
+        var b = a + step;
+        {
+            var c = b + a;
+            total = total + add(c) + counter.bump() - c - b;
+        }
+    }
+}
+print total;
+"#;
+
+fn time_run(path: &std::path::Path, flags: &[&str]) -> Duration {
+    let mut best: Duration = Duration::MAX;
+    for _ in 0..RUNS {
+        let start: Instant = Instant::now();
+        let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .args(flags)
+            .arg(path)
+            .output()
+            .unwrap();
+        let elapsed: Duration = start.elapsed();
+        assert!(output.status.success(), "{:?}", output);
+        best = best.min(elapsed);
+    }
+    best
+}
+
+const LOOKUPS: usize = 2_000_000;
+
+// The names scopes are keyed by, as a program's identifiers would be
+fn names() -> Vec<String> {
+    (0..32)
+        .map(|index| format!("variable_name_{}", index))
+        .collect()
+}
+
+// Environment lookups as they were before interning, hashing and comparing
+// the name's text every time
+fn time_string_lookups() -> Duration {
+    let names: Vec<String> = names();
+    let scope: HashMap<String, f64> = names.iter().map(|name| (name.clone(), 1.0)).collect();
+    let start: Instant = Instant::now();
+    let mut total: f64 = 0.0;
+    for index in 0..LOOKUPS {
+        total += scope[black_box(&names[index % names.len()])];
+    }
+    black_box(total);
+    start.elapsed()
+}
+
+// The same lookups keyed by symbols, as the tree-walker does now
+fn time_symbol_lookups() -> Duration {
+    let names: Vec<Symbol> = names().iter().map(|name| Symbol::intern(name)).collect();
+    let scope: HashMap<Symbol, f64> = names.iter().map(|name| (*name, 1.0)).collect();
+    let start: Instant = Instant::now();
+    let mut total: f64 = 0.0;
+    for index in 0..LOOKUPS {
+        total += scope[black_box(&names[index % names.len()])];
+    }
+    black_box(total);
+    start.elapsed()
+}
+
+fn main() {
+    let strings: Duration = (0..RUNS).map(|_| time_string_lookups()).min().unwrap();
+    let symbols: Duration = (0..RUNS).map(|_| time_symbol_lookups()).min().unwrap();
+    println!("lookups by String: {:?} (best of {})", strings, RUNS);
+    println!("lookups by Symbol: {:?} (best of {})", symbols, RUNS);
+    println!(
+        "symbol speedup: {:.1}x",
+        strings.as_secs_f64() / symbols.as_secs_f64()
+    );
+
+    let path = env::temp_dir().join("rlox_bench_variables.lox");
+    fs::write(&path, SCRIPT).unwrap();
+
+    for (name, flags) in [("tree-walker", &[][..]), ("vm", &["--vm"][..])] {
+        let best: Duration = time_run(&path, flags);
+        println!("variables ({}): {:?} (best of {})", name, best, RUNS);
+    }
+
+    let _ = fs::remove_file(&path);
+}
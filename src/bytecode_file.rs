@@ -0,0 +1,471 @@
+use std::rc::Rc;
+
+use crate::{
+    chunk::{Chunk, Constant, FunctionProto, OpCode, SpanStart},
+    compiler::Compiler,
+    error::LoxError,
+    lox::parse_program,
+    token::Span,
+};
+
+// Layout of a .loxc file, integers little endian:
+//
+//   magic    "LOXC"
+//   version  u16, bumped whenever the layout or the instruction set changes
+//   script   the top level function, see `Writer::function`
+//   checksum u32, CRC-32 of everything before it
+pub const FORMAT_VERSION: u16 = 1;
+const MAGIC: &[u8; 4] = b"LOXC";
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+// Compiles the source into the bytes of a .loxc file
+pub fn compile(source: &str) -> Result<Vec<u8>, Vec<LoxError>> {
+    let (statements, _) = parse_program(source)?;
+    let script: Rc<FunctionProto> = Compiler::compile(&statements)?;
+    Ok(encode(&script))
+}
+
+// True when the bytes look like a compiled file rather than source
+pub fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub(crate) fn encode(script: &FunctionProto) -> Vec<u8> {
+    let mut out: Writer = Writer { bytes: Vec::new() };
+    out.bytes.extend_from_slice(MAGIC);
+    out.u16(FORMAT_VERSION);
+    out.function(script);
+    let checksum: u32 = crc32(&out.bytes);
+    out.u32(checksum);
+    out.bytes
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<Rc<FunctionProto>, LoxError> {
+    if !is_compiled(bytes) {
+        return Err(LoxError::load(
+            "Not a compiled Lox file (bad magic number).",
+        ));
+    }
+    if bytes.len() < MAGIC.len() + 2 + 4 {
+        return Err(LoxError::load("Compiled file is truncated."));
+    }
+    let version: u16 = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+        return Err(LoxError::load(&format!(
+            "Compiled with bytecode format version {}, but this rlox reads version {}. Recompile the script.",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    let expected: u32 = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    if crc32(body) != expected {
+        return Err(LoxError::load(
+            "Checksum mismatch, the compiled file is corrupt or truncated.",
+        ));
+    }
+
+    let mut reader: Reader = Reader {
+        bytes: body,
+        position: MAGIC.len() + 2,
+    };
+    let script: FunctionProto = reader.function()?;
+    if script.arity != 0 || script.upvalue_count != 0 {
+        return Err(LoxError::load(
+            "The compiled script takes no arguments or upvalues.",
+        ));
+    }
+    if reader.position != body.len() {
+        return Err(LoxError::load("Unexpected data after the compiled script."));
+    }
+    Ok(Rc::new(script))
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    // name, arity, upvalue count, code, constant pool (nested functions
+    // inline), then the span table
+    fn function(&mut self, function: &FunctionProto) {
+        self.string(&function.name);
+        self.u8(function.arity);
+        self.u8(function.upvalue_count);
+
+        let chunk: &Chunk = &function.chunk;
+        self.u32(chunk.code.len() as u32);
+        self.bytes.extend_from_slice(&chunk.code);
+
+        self.u32(chunk.constants.len() as u32);
+        for constant in &chunk.constants {
+            match constant {
+                Constant::Number(val) => {
+                    self.u8(TAG_NUMBER);
+                    self.bytes.extend_from_slice(&val.to_bits().to_le_bytes());
+                }
+                Constant::Str(text) => {
+                    self.u8(TAG_STRING);
+                    self.string(text);
+                }
+                Constant::Function(nested) => {
+                    self.u8(TAG_FUNCTION);
+                    self.function(nested);
+                }
+            }
+        }
+
+        self.u32(chunk.spans.len() as u32);
+        for start in &chunk.spans {
+            self.u32(start.offset as u32);
+            self.u32(start.span.offset as u32);
+            self.u32(start.span.length as u32);
+            self.u32(start.span.line);
+            self.u32(start.span.column);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], LoxError> {
+        let end: usize = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| LoxError::load("Compiled file is truncated."))?;
+        let taken: &[u8] = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoxError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, LoxError> {
+        let bytes: &[u8] = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn f64(&mut self) -> Result<f64, LoxError> {
+        let bytes: &[u8] = self.take(8)?;
+        let mut bits: [u8; 8] = [0; 8];
+        bits.copy_from_slice(bytes);
+        Ok(f64::from_bits(u64::from_le_bytes(bits)))
+    }
+
+    fn string(&mut self) -> Result<String, LoxError> {
+        let length: usize = self.u32()? as usize;
+        let bytes: &[u8] = self.take(length)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| LoxError::load("Invalid UTF-8 in a string constant."))
+    }
+
+    fn function(&mut self) -> Result<FunctionProto, LoxError> {
+        let name: String = self.string()?;
+        let arity: u8 = self.u8()?;
+        let upvalue_count: u8 = self.u8()?;
+
+        let code_length: usize = self.u32()? as usize;
+        let code: Vec<u8> = self.take(code_length)?.to_vec();
+
+        let constant_count: usize = self.u32()? as usize;
+        let mut constants: Vec<Constant> = Vec::new();
+        for _ in 0..constant_count {
+            let constant: Constant = match self.u8()? {
+                TAG_NUMBER => Constant::Number(self.f64()?),
+                TAG_STRING => Constant::Str(self.string()?),
+                TAG_FUNCTION => Constant::Function(Rc::new(self.function()?)),
+                tag => {
+                    return Err(LoxError::load(&format!("Invalid constant tag {}.", tag)));
+                }
+            };
+            constants.push(constant);
+        }
+
+        let span_count: usize = self.u32()? as usize;
+        let mut spans: Vec<SpanStart> = Vec::new();
+        for _ in 0..span_count {
+            let offset: usize = self.u32()? as usize;
+            let span: Span = Span::new(
+                self.u32()? as usize,
+                self.u32()? as usize,
+                self.u32()?,
+                self.u32()?,
+            );
+            spans.push(SpanStart { offset, span });
+        }
+
+        let function: FunctionProto = FunctionProto {
+            name,
+            arity,
+            upvalue_count,
+            chunk: Chunk::from_parts(code, constants, spans),
+        };
+        verify(&function)?;
+        Ok(function)
+    }
+}
+
+// Checks that the VM can run the function without reading past its code,
+// constant pool or stack: a valid file that isn't corrupt can still be hand
+// crafted. What kind of value each instruction gets isn't tracked, the VM
+// reports the wrong kind as a runtime error.
+fn verify(function: &FunctionProto) -> Result<(), LoxError> {
+    let chunk: &Chunk = &function.chunk;
+    let malformed = &|offset: usize, problem: &str| -> LoxError {
+        let name: &str = if function.name.is_empty() {
+            "<script>"
+        } else {
+            &function.name
+        };
+        LoxError::load(&format!(
+            "Malformed bytecode in {} at offset {}: {}.",
+            name, offset, problem
+        ))
+    };
+
+    if chunk.spans.first().map(|start| start.offset) != Some(0) {
+        return Err(malformed(0, "missing source locations"));
+    }
+
+    // Jumps may only land on the start of an instruction
+    let mut starts: Vec<bool> = vec![false; chunk.code.len()];
+    let mut jumps: Vec<(usize, usize)> = Vec::new();
+    let mut offset: usize = 0;
+    let mut last: Option<OpCode> = None;
+    while offset < chunk.code.len() {
+        starts[offset] = true;
+        let op: OpCode = OpCode::from_byte(chunk.code[offset])
+            .ok_or_else(|| malformed(offset, "unknown opcode"))?;
+        let operands: usize = operand_len(op);
+        if offset + operands >= chunk.code.len() {
+            return Err(malformed(offset, "instruction runs past the end"));
+        }
+
+        let next: usize = offset + 1 + operands;
+        match op {
+            OpCode::Constant => match chunk.constants.get(chunk.read_u16(offset + 1) as usize) {
+                Some(Constant::Number(_)) | Some(Constant::Str(_)) => (),
+                _ => return Err(malformed(offset, "bad constant index")),
+            },
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => match chunk.constants.get(chunk.read_u16(offset + 1) as usize) {
+                Some(Constant::Str(_)) => (),
+                _ => return Err(malformed(offset, "bad name constant")),
+            },
+            OpCode::GetUpvalue | OpCode::SetUpvalue
+                if chunk.code[offset + 1] >= function.upvalue_count =>
+            {
+                return Err(malformed(offset, "bad upvalue index"));
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                jumps.push((offset, next + chunk.read_u16(offset + 1) as usize));
+            }
+            OpCode::Loop => match next.checked_sub(chunk.read_u16(offset + 1) as usize) {
+                Some(target) => jumps.push((offset, target)),
+                None => return Err(malformed(offset, "loop before the start")),
+            },
+            OpCode::Closure => {
+                let upvalue_count: usize =
+                    match chunk.constants.get(chunk.read_u16(offset + 1) as usize) {
+                        Some(Constant::Function(nested)) => nested.upvalue_count as usize,
+                        _ => return Err(malformed(offset, "bad function constant")),
+                    };
+                let end: usize = next + upvalue_count * 2;
+                if end >= chunk.code.len() {
+                    return Err(malformed(offset, "instruction runs past the end"));
+                }
+                for pair in chunk.code[next..end].chunks(2) {
+                    if pair[0] > 1 || (pair[0] == 0 && pair[1] >= function.upvalue_count) {
+                        return Err(malformed(offset, "bad upvalue index"));
+                    }
+                }
+                offset = end;
+                last = Some(op);
+                continue;
+            }
+            _ => (),
+        }
+        offset = next;
+        last = Some(op);
+    }
+
+    // Execution can only leave a function through a return
+    if last != Some(OpCode::Return) {
+        return Err(malformed(offset, "missing final return"));
+    }
+    for (offset, target) in jumps {
+        if !starts.get(target).copied().unwrap_or(false) {
+            return Err(malformed(offset, "jump to the middle of an instruction"));
+        }
+    }
+    verify_stack(function, malformed)
+}
+
+// Follows every path through the code counting the values the frame has on
+// the stack, so no instruction pops into the caller's frame or reads a local
+// slot that doesn't exist. Paths meeting at a jump target must agree, as the
+// compiler's output always does. Only called on code `verify` accepted.
+fn verify_stack(
+    function: &FunctionProto,
+    malformed: &dyn Fn(usize, &str) -> LoxError,
+) -> Result<(), LoxError> {
+    let chunk: &Chunk = &function.chunk;
+    let mut depths: Vec<Option<usize>> = vec![None; chunk.code.len()];
+    // The callee or receiver in slot 0, then the parameters
+    let mut pending: Vec<(usize, usize)> = vec![(0, function.arity as usize + 1)];
+
+    while let Some((offset, depth)) = pending.pop() {
+        match depths[offset] {
+            Some(seen) if seen == depth => continue,
+            Some(_) => return Err(malformed(offset, "stack depth differs between paths")),
+            None => depths[offset] = Some(depth),
+        }
+
+        let op: OpCode = OpCode::from_byte(chunk.code[offset]).expect("opcode checked");
+        let (pops, pushes): (usize, usize) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetLocal
+            | OpCode::GetGlobal
+            | OpCode::GetUpvalue
+            | OpCode::Closure
+            | OpCode::Class => (0, 1),
+            OpCode::Pop
+            | OpCode::DefineGlobal
+            | OpCode::Print
+            | OpCode::CloseUpvalue
+            | OpCode::Return => (1, 0),
+            OpCode::SetLocal
+            | OpCode::SetGlobal
+            | OpCode::SetUpvalue
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Inherit
+            | OpCode::Method => (2, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            // The callee and its arguments make way for the result
+            OpCode::Call => (chunk.code[offset + 1] as usize + 1, 1),
+        };
+        if depth < pops {
+            return Err(malformed(offset, "stack underflow"));
+        }
+        let after: usize = depth - pops + pushes;
+
+        let next: usize = offset + 1 + operand_len(op);
+        match op {
+            OpCode::GetLocal | OpCode::SetLocal if chunk.code[offset + 1] as usize >= depth => {
+                return Err(malformed(offset, "bad local slot"));
+            }
+            OpCode::Closure => {
+                let upvalue_count: usize =
+                    match &chunk.constants[chunk.read_u16(offset + 1) as usize] {
+                        Constant::Function(nested) => nested.upvalue_count as usize,
+                        _ => unreachable!("constant checked"),
+                    };
+                let end: usize = next + upvalue_count * 2;
+                for pair in chunk.code[next..end].chunks(2) {
+                    if pair[0] == 1 && pair[1] as usize >= depth {
+                        return Err(malformed(offset, "bad local slot"));
+                    }
+                }
+                pending.push((end, after));
+            }
+            OpCode::Return => (),
+            OpCode::Jump => pending.push((next + chunk.read_u16(offset + 1) as usize, after)),
+            OpCode::Loop => pending.push((next - chunk.read_u16(offset + 1) as usize, after)),
+            OpCode::JumpIfFalse => {
+                pending.push((next + chunk.read_u16(offset + 1) as usize, after));
+                pending.push((next, after));
+            }
+            _ => pending.push((next, after)),
+        }
+    }
+    Ok(())
+}
+
+// Bytes of operands following the opcode, not counting a closure's upvalue
+// pairs
+fn operand_len(op: OpCode) -> usize {
+    match op {
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => 1,
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method
+        | OpCode::Jump
+        | OpCode::JumpIfFalse
+        | OpCode::Loop
+        | OpCode::Closure => 2,
+        _ => 0,
+    }
+}
+
+// CRC-32 (IEEE), bit at a time since files are small and read once
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask: u32 = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,213 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::token::Span;
+
+// Instructions of the bytecode VM. Operands follow the opcode byte: u8 for
+// stack slots, upvalues and argument counts, u16 (big endian) for constant
+// indices and jump distances.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    // Constant index of the function, then a (is_local, index) byte pair
+    // for each upvalue it captures
+    Closure,
+    CloseUpvalue,
+    Return,
+    Class,
+    Inherit,
+    Method,
+}
+
+// Indexed by opcode byte
+const OPCODES: [OpCode; 37] = [
+    OpCode::Constant,
+    OpCode::Nil,
+    OpCode::True,
+    OpCode::False,
+    OpCode::Pop,
+    OpCode::GetLocal,
+    OpCode::SetLocal,
+    OpCode::GetGlobal,
+    OpCode::DefineGlobal,
+    OpCode::SetGlobal,
+    OpCode::GetUpvalue,
+    OpCode::SetUpvalue,
+    OpCode::GetProperty,
+    OpCode::SetProperty,
+    OpCode::GetSuper,
+    OpCode::Equal,
+    OpCode::Greater,
+    OpCode::GreaterEqual,
+    OpCode::Less,
+    OpCode::LessEqual,
+    OpCode::Add,
+    OpCode::Subtract,
+    OpCode::Multiply,
+    OpCode::Divide,
+    OpCode::Not,
+    OpCode::Negate,
+    OpCode::Print,
+    OpCode::Jump,
+    OpCode::JumpIfFalse,
+    OpCode::Loop,
+    OpCode::Call,
+    OpCode::Closure,
+    OpCode::CloseUpvalue,
+    OpCode::Return,
+    OpCode::Class,
+    OpCode::Inherit,
+    OpCode::Method,
+];
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OPCODES.get(byte as usize).copied()
+    }
+}
+
+// Values known at compile time, stored in a chunk's constant table
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Number(f64),
+    Str(String),
+    Function(Rc<FunctionProto>),
+}
+
+// A compiled function, the top level script being one without a name
+#[derive(Debug)]
+pub struct FunctionProto {
+    pub name: String,
+    pub arity: u8,
+    pub upvalue_count: u8,
+    pub chunk: Chunk,
+}
+
+// Source location of the instructions from `offset` up to the next entry
+#[derive(Debug, Clone, Copy)]
+pub struct SpanStart {
+    pub offset: usize,
+    pub span: Span,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    // Run-length encoded, a new entry only when the span changes
+    pub spans: Vec<SpanStart>,
+    // Where each number (by bits) and string already sits in `constants`
+    numbers: HashMap<u64, u16>,
+    strings: HashMap<String, u16>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A chunk read back from a compiled file, ready to run but not to extend
+    pub fn from_parts(code: Vec<u8>, constants: Vec<Constant>, spans: Vec<SpanStart>) -> Self {
+        Self {
+            code,
+            constants,
+            spans,
+            ..Self::default()
+        }
+    }
+
+    pub fn write(&mut self, byte: u8, span: Span) {
+        let changed: bool = match self.spans.last() {
+            Some(last) => last.span != span,
+            None => true,
+        };
+        if changed {
+            self.spans.push(SpanStart {
+                offset: self.code.len(),
+                span,
+            });
+        }
+        self.code.push(byte);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.write(op as u8, span);
+    }
+
+    pub fn write_u16(&mut self, value: u16, span: Span) {
+        let [high, low] = value.to_be_bytes();
+        self.write(high, span);
+        self.write(low, span);
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    // Index of the constant, reusing an equal number or string already in
+    // the table. None once the table is full.
+    pub fn add_constant(&mut self, constant: Constant) -> Option<u16> {
+        let existing: Option<&u16> = match &constant {
+            Constant::Number(val) => self.numbers.get(&val.to_bits()),
+            Constant::Str(val) => self.strings.get(val),
+            Constant::Function(_) => None,
+        };
+        if let Some(index) = existing {
+            return Some(*index);
+        }
+
+        if self.constants.len() > u16::MAX as usize {
+            return None;
+        }
+        let index: u16 = self.constants.len() as u16;
+        match &constant {
+            Constant::Number(val) => {
+                self.numbers.insert(val.to_bits(), index);
+            }
+            Constant::Str(val) => {
+                self.strings.insert(val.clone(), index);
+            }
+            Constant::Function(_) => (),
+        }
+        self.constants.push(constant);
+        Some(index)
+    }
+
+    pub fn span_at(&self, offset: usize) -> Span {
+        let index: usize = self
+            .spans
+            .partition_point(|start| start.offset <= offset)
+            .saturating_sub(1);
+        self.spans[index].span
+    }
+}
@@ -0,0 +1,597 @@
+use std::rc::Rc;
+
+use crate::{
+    chunk::{Chunk, Constant, FunctionProto, OpCode},
+    error::LoxError,
+    expr::{Expr, LiteralRepresentations},
+    stmt::{self, Stmt},
+    symbol::Symbol,
+    token::{Span, Token},
+    token_type::TokenType,
+};
+
+// Operands addressing locals and upvalues are a single byte
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: Symbol,
+    // None between declaring the variable and finishing its initializer
+    depth: Option<usize>,
+    // Captured locals are moved off the stack when their scope ends
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+// The function being compiled, one per level of nesting
+struct FunctionState {
+    name: String,
+    kind: FunctionKind,
+    arity: u8,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> Self {
+        // Slot 0 holds the receiver in methods and the callee otherwise,
+        // which can't be named from Lox
+        let slot_zero: &str = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        Self {
+            name: name.to_string(),
+            kind,
+            arity: 0,
+            chunk: Chunk::new(),
+            locals: vec![Local {
+                name: Symbol::intern(slot_zero),
+                depth: Some(0),
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        }
+    }
+}
+
+// Where a variable lives, decided at compile time
+enum Resolved {
+    Local(u8),
+    Upvalue(u8),
+    Global(u16),
+}
+
+// Turns a resolved program into bytecode for the VM. The AST has already
+// been checked by the resolver, so only the VM's own limits are reported.
+pub struct Compiler {
+    functions: Vec<FunctionState>,
+    errors: Vec<LoxError>,
+}
+
+impl Compiler {
+    pub fn compile(statements: &[Stmt]) -> Result<Rc<FunctionProto>, Vec<LoxError>> {
+        let mut compiler: Compiler = Compiler {
+            functions: vec![FunctionState::new("", FunctionKind::Script)],
+            errors: Vec::new(),
+        };
+
+        // Like the tree-walker, a script ending in an expression statement
+        // returns its value
+        let (last, rest): (Option<&Stmt>, &[Stmt]) = match statements.split_last() {
+            Some((last @ Stmt::Expression(_), rest)) => (Some(last), rest),
+            _ => (None, statements),
+        };
+        for statement in rest {
+            compiler.statement(statement);
+        }
+        let end: Span = statements.last().map_or(Span::new(0, 0, 1, 1), Stmt::span);
+        if let Some(Stmt::Expression(stmt)) = last {
+            compiler.expression(&stmt.expression);
+            compiler.emit(OpCode::Return, stmt.span);
+        }
+        let script: FunctionProto = compiler.end_function(end);
+
+        if !compiler.errors.is_empty() {
+            return Err(compiler.errors);
+        }
+        Ok(Rc::new(script))
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions
+            .last_mut()
+            .expect("Compiler always has a function")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().chunk
+    }
+
+    fn emit(&mut self, op: OpCode, span: Span) {
+        self.chunk().write_op(op, span);
+    }
+
+    fn emit_byte(&mut self, op: OpCode, operand: u8, span: Span) {
+        self.chunk().write_op(op, span);
+        self.chunk().write(operand, span);
+    }
+
+    fn emit_u16(&mut self, op: OpCode, operand: u16, span: Span) {
+        self.chunk().write_op(op, span);
+        self.chunk().write_u16(operand, span);
+    }
+
+    fn make_constant(&mut self, constant: Constant, span: Span) -> u16 {
+        match self.chunk().add_constant(constant) {
+            Some(index) => index,
+            None => {
+                self.errors
+                    .push(LoxError::compile(span, "Too many constants in one chunk."));
+                0
+            }
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> u16 {
+        self.make_constant(Constant::Str(name.lexeme.to_string()), name.span)
+    }
+
+    // Emits a jump with a placeholder distance, returning where to patch it
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_u16(op, u16::MAX, span);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, operand: usize, span: Span) {
+        let distance: usize = self.chunk().code.len() - operand - 2;
+        if distance > u16::MAX as usize {
+            self.errors
+                .push(LoxError::compile(span, "Too much code to jump over."));
+            return;
+        }
+        let [high, low] = (distance as u16).to_be_bytes();
+        self.chunk().code[operand] = high;
+        self.chunk().code[operand + 1] = low;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        // The distance also covers the Loop instruction itself
+        let distance: usize = self.chunk().code.len() + 3 - loop_start;
+        if distance > u16::MAX as usize {
+            self.errors
+                .push(LoxError::compile(span, "Loop body too large."));
+        }
+        self.emit_u16(OpCode::Loop, distance.min(u16::MAX as usize) as u16, span);
+    }
+
+    fn emit_return(&mut self, span: Span) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_byte(OpCode::GetLocal, 0, span);
+        } else {
+            self.emit(OpCode::Nil, span);
+        }
+        self.emit(OpCode::Return, span);
+    }
+
+    fn end_function(&mut self, span: Span) -> FunctionProto {
+        self.emit_return(span);
+        let state: FunctionState = self.functions.pop().expect("Function to end");
+        FunctionProto {
+            name: state.name,
+            arity: state.arity,
+            upvalue_count: state.upvalues.len() as u8,
+            chunk: state.chunk,
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self, span: Span) {
+        self.current().scope_depth -= 1;
+        loop {
+            let state: &FunctionState = self.current();
+            let captured: bool = match state.locals.last() {
+                Some(local) if local.depth.is_some_and(|depth| depth > state.scope_depth) => {
+                    local.is_captured
+                }
+                _ => break,
+            };
+            if captured {
+                self.emit(OpCode::CloseUpvalue, span);
+            } else {
+                self.emit(OpCode::Pop, span);
+            }
+            self.current().locals.pop();
+        }
+    }
+
+    fn add_local(&mut self, name: Symbol, span: Span) {
+        if self.current().locals.len() == MAX_LOCALS {
+            self.errors.push(LoxError::compile(
+                span,
+                "Too many local variables in function.",
+            ));
+            return;
+        }
+        self.current().locals.push(Local {
+            name,
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    fn mark_initialized(&mut self) {
+        let state: &mut FunctionState = self.current();
+        let depth: usize = state.scope_depth;
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = Some(depth);
+        }
+    }
+
+    // Locals are declared before their initializer runs, globals are only
+    // bound by `define_variable` afterwards
+    fn declare_variable(&mut self, name: &Token) {
+        if self.current().scope_depth > 0 {
+            self.add_local(name.lexeme, name.span);
+        }
+    }
+
+    fn define_variable(&mut self, name: &Token, span: Span) {
+        if self.current().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        let global: u16 = self.identifier_constant(name);
+        self.emit_u16(OpCode::DefineGlobal, global, span);
+    }
+
+    fn resolve_local(&self, function: usize, name: Symbol) -> Option<u8> {
+        self.functions[function]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    fn resolve_upvalue(&mut self, function: usize, name: &Token) -> Option<u8> {
+        if function == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name.lexeme) {
+            self.functions[function - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, slot, true, name.span));
+        }
+        let index: u8 = self.resolve_upvalue(function - 1, name)?;
+        Some(self.add_upvalue(function, index, false, name.span))
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool, span: Span) -> u8 {
+        let upvalue: UpvalueRef = UpvalueRef { index, is_local };
+        let upvalues: &mut Vec<UpvalueRef> = &mut self.functions[function].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            self.errors.push(LoxError::compile(
+                span,
+                "Too many closure variables in function.",
+            ));
+            return 0;
+        }
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    fn resolve(&mut self, name: &Token) -> Resolved {
+        let top: usize = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(top, name.lexeme) {
+            return Resolved::Local(slot);
+        }
+        if let Some(index) = self.resolve_upvalue(top, name) {
+            return Resolved::Upvalue(index);
+        }
+        Resolved::Global(self.identifier_constant(name))
+    }
+
+    fn get_variable(&mut self, name: &Token, span: Span) {
+        match self.resolve(name) {
+            Resolved::Local(slot) => self.emit_byte(OpCode::GetLocal, slot, span),
+            Resolved::Upvalue(index) => self.emit_byte(OpCode::GetUpvalue, index, span),
+            Resolved::Global(global) => self.emit_u16(OpCode::GetGlobal, global, span),
+        }
+    }
+
+    fn set_variable(&mut self, name: &Token, span: Span) {
+        match self.resolve(name) {
+            Resolved::Local(slot) => self.emit_byte(OpCode::SetLocal, slot, span),
+            Resolved::Upvalue(index) => self.emit_byte(OpCode::SetUpvalue, index, span),
+            Resolved::Global(global) => self.emit_u16(OpCode::SetGlobal, global, span),
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression(stmt) => {
+                self.expression(&stmt.expression);
+                self.emit(OpCode::Pop, stmt.span);
+            }
+            Stmt::Print(stmt) => {
+                self.expression(&stmt.print_expression);
+                self.emit(OpCode::Print, stmt.span);
+            }
+            Stmt::Var(stmt) => {
+                self.declare_variable(&stmt.name);
+                match &stmt.initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => self.emit(OpCode::Nil, stmt.span),
+                }
+                self.define_variable(&stmt.name, stmt.span);
+            }
+            Stmt::Block(stmt) => {
+                self.begin_scope();
+                for inner in &stmt.statements {
+                    self.statement(inner);
+                }
+                self.end_scope(stmt.span);
+            }
+            Stmt::If(stmt) => self.if_statement(stmt),
+            Stmt::While(stmt) => self.while_statement(stmt),
+            Stmt::Function(stmt) => {
+                // Declared before the body so the function can call itself
+                self.declare_variable(&stmt.name);
+                if self.current().scope_depth > 0 {
+                    self.mark_initialized();
+                }
+                self.function(stmt, FunctionKind::Function);
+                self.define_variable(&stmt.name, stmt.span);
+            }
+            Stmt::Return(stmt) => match &stmt.value {
+                Some(value) => {
+                    self.expression(value);
+                    self.emit(OpCode::Return, stmt.span);
+                }
+                None => self.emit_return(stmt.span),
+            },
+            Stmt::Class(stmt) => self.class(stmt),
+        }
+    }
+
+    fn if_statement(&mut self, stmt: &stmt::If) {
+        self.expression(&stmt.condition);
+        let then_jump: usize = self.emit_jump(OpCode::JumpIfFalse, stmt.span);
+        self.emit(OpCode::Pop, stmt.span);
+        self.statement(&stmt.then_branch);
+
+        let else_jump: usize = self.emit_jump(OpCode::Jump, stmt.span);
+        self.patch_jump(then_jump, stmt.span);
+        self.emit(OpCode::Pop, stmt.span);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.statement(else_branch);
+        }
+        self.patch_jump(else_jump, stmt.span);
+    }
+
+    fn while_statement(&mut self, stmt: &stmt::While) {
+        let loop_start: usize = self.chunk().code.len();
+        self.expression(&stmt.condition);
+        let exit_jump: usize = self.emit_jump(OpCode::JumpIfFalse, stmt.span);
+        self.emit(OpCode::Pop, stmt.span);
+        self.statement(&stmt.body);
+        self.emit_loop(loop_start, stmt.span);
+
+        self.patch_jump(exit_jump, stmt.span);
+        self.emit(OpCode::Pop, stmt.span);
+    }
+
+    // Compiles the body into its own prototype and emits the closure that
+    // creates it at runtime
+    fn function(&mut self, declaration: &stmt::Function, kind: FunctionKind) {
+        self.functions
+            .push(FunctionState::new(declaration.name.lexeme.as_str(), kind));
+        self.begin_scope();
+        for param in &declaration.params {
+            self.current().arity += 1;
+            self.add_local(param.lexeme, param.span);
+            self.mark_initialized();
+        }
+        for statement in &declaration.body {
+            self.statement(statement);
+        }
+
+        let upvalues: Vec<UpvalueRef> = self.current().upvalues.clone();
+        let proto: FunctionProto = self.end_function(declaration.span);
+        let constant: u16 =
+            self.make_constant(Constant::Function(Rc::new(proto)), declaration.span);
+        self.emit_u16(OpCode::Closure, constant, declaration.span);
+        for upvalue in upvalues {
+            self.chunk().write(upvalue.is_local as u8, declaration.span);
+            self.chunk().write(upvalue.index, declaration.span);
+        }
+    }
+
+    fn class(&mut self, stmt: &stmt::Class) {
+        let name_constant: u16 = self.identifier_constant(&stmt.name);
+        self.declare_variable(&stmt.name);
+        self.emit_u16(OpCode::Class, name_constant, stmt.span);
+        self.define_variable(&stmt.name, stmt.span);
+
+        let has_superclass: bool = stmt.superclass.is_some();
+        if let Some(superclass) = &stmt.superclass {
+            self.expression(superclass);
+            // Methods capture the superclass through a local named `super`
+            self.begin_scope();
+            self.add_local(Symbol::super_(), superclass.span());
+            self.mark_initialized();
+
+            self.get_variable(&stmt.name, stmt.span);
+            self.emit(OpCode::Inherit, superclass.span());
+        }
+
+        self.get_variable(&stmt.name, stmt.span);
+        for method in &stmt.methods {
+            let method_constant: u16 = self.identifier_constant(&method.name);
+            let kind: FunctionKind = if method.name.lexeme == Symbol::init() {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(method, kind);
+            self.emit_u16(OpCode::Method, method_constant, method.span);
+        }
+        self.emit(OpCode::Pop, stmt.span);
+
+        if has_superclass {
+            self.end_scope(stmt.span);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { literal, span } => match literal {
+                LiteralRepresentations::CustomNil => self.emit(OpCode::Nil, *span),
+                LiteralRepresentations::CustomBoolean { val: true } => {
+                    self.emit(OpCode::True, *span)
+                }
+                LiteralRepresentations::CustomBoolean { val: false } => {
+                    self.emit(OpCode::False, *span)
+                }
+                LiteralRepresentations::CustomNumber { val } => {
+                    let constant: u16 = self.make_constant(Constant::Number(*val), *span);
+                    self.emit_u16(OpCode::Constant, constant, *span);
+                }
+                LiteralRepresentations::CustomString { val } => {
+                    let constant: u16 = self.make_constant(Constant::Str(val.to_string()), *span);
+                    self.emit_u16(OpCode::Constant, constant, *span);
+                }
+            },
+            Expr::Grouping { expr, .. } => self.expression(expr),
+            Expr::Unary {
+                operator, right, ..
+            } => {
+                self.expression(right);
+                match operator.token_type {
+                    TokenType::MINUS => self.emit(OpCode::Negate, operator.span),
+                    _ => self.emit(OpCode::Not, operator.span),
+                }
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                span,
+            } => {
+                self.expression(left);
+                self.expression(right);
+                self.binary_op(operator.token_type, *span);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                span,
+            } => {
+                self.expression(left);
+                if operator.token_type == TokenType::OR {
+                    // Falls through to the right operand only when falsey
+                    let else_jump: usize = self.emit_jump(OpCode::JumpIfFalse, *span);
+                    let end_jump: usize = self.emit_jump(OpCode::Jump, *span);
+                    self.patch_jump(else_jump, *span);
+                    self.emit(OpCode::Pop, *span);
+                    self.expression(right);
+                    self.patch_jump(end_jump, *span);
+                } else {
+                    let end_jump: usize = self.emit_jump(OpCode::JumpIfFalse, *span);
+                    self.emit(OpCode::Pop, *span);
+                    self.expression(right);
+                    self.patch_jump(end_jump, *span);
+                }
+            }
+            Expr::Variable { name, span, .. } => self.get_variable(name, *span),
+            Expr::Assign { name, value, .. } => {
+                self.expression(value);
+                self.set_variable(name, name.span);
+            }
+            Expr::Call {
+                callee,
+                arguments,
+                span,
+                ..
+            } => {
+                self.expression(callee);
+                for argument in arguments {
+                    self.expression(argument);
+                }
+                self.emit_byte(OpCode::Call, arguments.len() as u8, *span);
+            }
+            Expr::Get { object, name, .. } => {
+                self.expression(object);
+                let constant: u16 = self.identifier_constant(name);
+                self.emit_u16(OpCode::GetProperty, constant, name.span);
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(value);
+                let constant: u16 = self.identifier_constant(name);
+                self.emit_u16(OpCode::SetProperty, constant, name.span);
+            }
+            Expr::This { keyword, span, .. } => self.get_variable(keyword, *span),
+            Expr::Super {
+                keyword,
+                method,
+                span,
+                ..
+            } => {
+                let this: Token = Token::new(TokenType::THIS, Symbol::this(), None, *span);
+                self.get_variable(&this, *span);
+                self.get_variable(keyword, *span);
+                let constant: u16 = self.identifier_constant(method);
+                self.emit_u16(OpCode::GetSuper, constant, *span);
+            }
+        }
+    }
+
+    fn binary_op(&mut self, operator: TokenType, span: Span) {
+        match operator {
+            TokenType::PLUS => self.emit(OpCode::Add, span),
+            TokenType::MINUS => self.emit(OpCode::Subtract, span),
+            TokenType::STAR => self.emit(OpCode::Multiply, span),
+            TokenType::SLASH => self.emit(OpCode::Divide, span),
+            TokenType::EQUALEQUAL => self.emit(OpCode::Equal, span),
+            TokenType::BANGEQUAL => {
+                self.emit(OpCode::Equal, span);
+                self.emit(OpCode::Not, span);
+            }
+            TokenType::GREATER => self.emit(OpCode::Greater, span),
+            TokenType::GREATEREQUAL => self.emit(OpCode::GreaterEqual, span),
+            TokenType::LESS => self.emit(OpCode::Less, span),
+            TokenType::LESSEQUAL => self.emit(OpCode::LessEqual, span),
+            _ => self
+                .errors
+                .push(LoxError::compile(span, "Unknown binary operator.")),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt::Write;
+
+use crate::{error::LoxError, token::Span};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// Renders errors rustc style, with the offending source line and the span
+// underlined:
+//
+// error: Expect expression.
+//  --> script.lox:1:10
+//   |
+// 1 | print 1 +;
+//   |          ^
+pub struct Renderer<'a> {
+    source: &'a str,
+    file_name: &'a str,
+    // Plain output leaves out the colour escapes, for logs and pipes
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source: &'a str, file_name: &'a str, color: bool) -> Self {
+        Self {
+            source,
+            file_name,
+            color,
+        }
+    }
+
+    pub fn render(&self, error: &LoxError) -> String {
+        let mut out: String = String::new();
+
+        let kind: &str = if error.is_runtime() {
+            "runtime error"
+        } else {
+            "error"
+        };
+        let _ = writeln!(
+            out,
+            "{}: {}",
+            self.paint(RED, kind),
+            self.paint(BOLD, error.message())
+        );
+
+        if let Some(span) = error.span() {
+            self.snippet(&mut out, span, RED);
+        }
+
+        for note in error.notes() {
+            let _ = writeln!(out, "{}: {}", self.paint(CYAN, "note"), note.message);
+            self.snippet(&mut out, note.span, CYAN);
+        }
+
+        out
+    }
+
+    fn snippet(&self, out: &mut String, span: Span, underline_color: &str) {
+        // Without the source, e.g. for a compiled script, only say where
+        if self
+            .source
+            .get(span.offset..span.offset + span.length)
+            .is_none()
+        {
+            let _ = writeln!(
+                out,
+                " {} {}:{}:{}",
+                self.paint(BLUE, "-->"),
+                self.file_name,
+                span.line,
+                span.column
+            );
+            return;
+        }
+
+        let offset: usize = span.offset.min(self.source.len());
+        let line_start: usize = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end: usize = self.source[offset..]
+            .find('\n')
+            .map_or(self.source.len(), |i| offset + i);
+        let line: &str = self.source[line_start..line_end].trim_end_matches('\r');
+
+        // Spans running over several lines are only underlined on the first
+        let before: usize = self.source[line_start..offset].chars().count();
+        let span_end: usize = (offset + span.length).min(line_start + line.len());
+        let width: usize = self.source[offset..span_end.max(offset)].chars().count();
+        let column: usize = before + 1;
+
+        let line_number: String = span.line.to_string();
+        let gutter: String = " ".repeat(line_number.len());
+        let bar: String = self.paint(BLUE, "|");
+
+        let _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            gutter,
+            self.paint(BLUE, "-->"),
+            self.file_name,
+            span.line,
+            column
+        );
+        let _ = writeln!(out, "{} {}", gutter, bar);
+        let _ = writeln!(out, "{} {} {}", self.paint(BLUE, &line_number), bar, line);
+        let _ = writeln!(
+            out,
+            "{} {} {}{}",
+            gutter,
+            bar,
+            " ".repeat(before),
+            self.paint(underline_color, &"^".repeat(width.max(1)))
+        );
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
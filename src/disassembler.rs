@@ -0,0 +1,185 @@
+use std::fmt::Write;
+
+use crate::{
+    bytecode_file,
+    chunk::{Chunk, Constant, FunctionProto, OpCode},
+    compiler::Compiler,
+    error::LoxError,
+    lox::parse_program,
+    value::format_number,
+};
+
+// Compiles the source and lists the bytecode of the script and of every
+// function in it, for debugging the compiler:
+//
+// == <script> ==
+// -- 1 | print 1 + 2;
+// 0000    1 Constant            0 '1'
+// 0003    | Constant            1 '2'
+// 0006    | Add
+// 0007    | Print
+pub fn disassemble(source: &str) -> Result<String, Vec<LoxError>> {
+    let (statements, _) = parse_program(source)?;
+    let script = Compiler::compile(&statements)?;
+    let lines: Vec<&str> = source.lines().collect();
+
+    let mut out: String = String::new();
+    disassemble_function(&script, &lines, &mut out);
+    Ok(out)
+}
+
+// Lists a compiled .loxc file, which has locations but no source lines
+pub fn disassemble_compiled(bytes: &[u8]) -> Result<String, LoxError> {
+    let script = bytecode_file::decode(bytes)?;
+    let mut out: String = String::new();
+    disassemble_function(&script, &[], &mut out);
+    Ok(out)
+}
+
+// Nested functions are listed after the chunk that creates them
+fn disassemble_function(function: &FunctionProto, lines: &[&str], out: &mut String) {
+    let _ = writeln!(out, "== {} ==", function_name(function));
+
+    let chunk: &Chunk = &function.chunk;
+    let mut last_line: u32 = 0;
+    let mut offset: usize = 0;
+    while offset < chunk.code.len() {
+        let line: u32 = chunk.span_at(offset).line;
+        if line != last_line {
+            if let Some(text) = lines.get(line as usize - 1) {
+                let _ = writeln!(out, "-- {} | {}", line, text.trim());
+            }
+            last_line = line;
+        }
+        offset = disassemble_instruction(chunk, offset, out);
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(nested) = constant {
+            let _ = writeln!(out);
+            disassemble_function(nested, lines, out);
+        }
+    }
+}
+
+// Writes one instruction and gives the offset of the next
+pub(crate) fn disassemble_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> usize {
+    let _ = write!(out, "{:04} ", offset);
+    let line: u32 = chunk.span_at(offset).line;
+    if offset > 0 && chunk.span_at(offset - 1).line == line {
+        let _ = write!(out, "   | ");
+    } else {
+        let _ = write!(out, "{:4} ", line);
+    }
+
+    let byte: u8 = chunk.code[offset];
+    let op: OpCode = match OpCode::from_byte(byte) {
+        Some(op) => op,
+        None => {
+            let _ = writeln!(out, "Unknown opcode {}", byte);
+            return offset + 1;
+        }
+    };
+    let name: String = format!("{:?}", op);
+
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index: u16 = operand_u16(chunk, offset + 1);
+            let _ = writeln!(
+                out,
+                "{:<16} {:4} '{}'",
+                name,
+                index,
+                constant_text(chunk, index)
+            );
+            offset + 3
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => {
+            let _ = writeln!(out, "{:<16} {:4}", name, operand_u8(chunk, offset + 1));
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let distance: usize = operand_u16(chunk, offset + 1) as usize;
+            let target: isize = if op == OpCode::Loop {
+                (offset + 3) as isize - distance as isize
+            } else {
+                (offset + 3 + distance) as isize
+            };
+            let _ = writeln!(out, "{:<16} {:4} -> {}", name, offset, target);
+            offset + 3
+        }
+        OpCode::Closure => {
+            let index: u16 = operand_u16(chunk, offset + 1);
+            let _ = writeln!(
+                out,
+                "{:<16} {:4} {}",
+                name,
+                index,
+                constant_text(chunk, index)
+            );
+            let upvalue_count: u8 = match chunk.constants.get(index as usize) {
+                Some(Constant::Function(function)) => function.upvalue_count,
+                _ => 0,
+            };
+            let mut next: usize = offset + 3;
+            for _ in 0..upvalue_count {
+                let kind: &str = if operand_u8(chunk, next) == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                let _ = writeln!(
+                    out,
+                    "{:04}    |                     {} {}",
+                    next,
+                    kind,
+                    operand_u8(chunk, next + 1)
+                );
+                next += 2;
+            }
+            next
+        }
+        _ => {
+            let _ = writeln!(out, "{}", name);
+            offset + 1
+        }
+    }
+}
+
+// Operands past the end of a truncated chunk read as zero
+fn operand_u8(chunk: &Chunk, offset: usize) -> u8 {
+    chunk.code.get(offset).copied().unwrap_or(0)
+}
+
+fn operand_u16(chunk: &Chunk, offset: usize) -> u16 {
+    u16::from_be_bytes([operand_u8(chunk, offset), operand_u8(chunk, offset + 1)])
+}
+
+fn constant_text(chunk: &Chunk, index: u16) -> String {
+    match chunk.constants.get(index as usize) {
+        Some(Constant::Number(val)) => format_number(*val),
+        Some(Constant::Str(text)) => text.clone(),
+        Some(Constant::Function(function)) => format!("<fn {}>", function_name(function)),
+        None => "<missing constant>".to_string(),
+    }
+}
+
+fn function_name(function: &FunctionProto) -> &str {
+    if function.name.is_empty() {
+        "<script>"
+    } else {
+        &function.name
+    }
+}
@@ -1,39 +1,103 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{expr::LiteralRepresentations, token::Token};
-
-#[derive(Debug)]
-pub struct EnvironmentErr {
-    reason: String,
-}
+use crate::{error::LoxError, symbol::Symbol, token::Token, value::Value};
 
 pub struct Environment {
-    pub values: HashMap<String, LiteralRepresentations>,
+    // Keyed by interned name, so lookups hash and compare a pointer
+    pub values: HashMap<Symbol, Value>,
+    // Scope this one is nested in, None for the global scope. Shared since
+    // closures keep their defining scope alive after it has been exited.
+    pub enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
             values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
         }
     }
 
-    pub fn define(&mut self, name: String, value: LiteralRepresentations) {
+    pub fn define(&mut self, name: Symbol, value: Value) {
         self.values.insert(name, value);
     }
 
-    pub fn get(&self, name: Token) -> Result<LiteralRepresentations, EnvironmentErr> {
-        if self.values.contains_key(&name.lexeme) {
-            match self.values.get(&name.lexeme) {
-                Some(val) => return Ok(val.clone()),
-                None => Err(EnvironmentErr {
-                    reason: format!("Environment coult not find key {}", &name.lexeme),
-                }),
+    pub fn assign(&mut self, name: Token, value: Value) -> Result<(), LoxError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => {
+                let message: String = format!("Undefined variable '{}'.", &name.lexeme);
+                Err(LoxError::runtime(name, &message))
+            }
+        }
+    }
+
+    // Reads a variable the resolver found `distance` scopes out
+    pub fn get_at(&self, distance: usize, name: Token) -> Result<Value, LoxError> {
+        if distance == 0 {
+            return match self.values.get(&name.lexeme) {
+                Some(val) => Ok(val.clone()),
+                None => {
+                    let message: String = format!("Undefined variable '{}'.", &name.lexeme);
+                    Err(LoxError::runtime(name, &message))
+                }
+            };
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(distance - 1, name),
+            None => {
+                let message: String =
+                    format!("No scope at depth {} for '{}'.", distance, &name.lexeme);
+                Err(LoxError::runtime(name, &message))
+            }
+        }
+    }
+
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: Token,
+        value: Value,
+    ) -> Result<(), LoxError> {
+        if distance == 0 {
+            self.values.insert(name.lexeme, value);
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => {
+                let message: String =
+                    format!("No scope at depth {} for '{}'.", distance, &name.lexeme);
+                Err(LoxError::runtime(name, &message))
+            }
+        }
+    }
+
+    pub fn get(&self, name: Token) -> Result<Value, LoxError> {
+        if let Some(val) = self.values.get(&name.lexeme) {
+            return Ok(val.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => {
+                let message: String = format!("Undefined variable '{}'.", &name.lexeme);
+                Err(LoxError::runtime(name, &message))
             }
-        } else {
-            Err(EnvironmentErr {
-                reason: format!("Undefined variable '{}'.", &name.lexeme),
-            })
         }
     }
 }
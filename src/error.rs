@@ -0,0 +1,220 @@
+use std::fmt;
+
+use crate::{
+    token::{Span, Token},
+    token_type::TokenType,
+};
+
+// Every phase reports its problems as a LoxError, the driver decides how and
+// when to show them. The span is the offending source text, by default the
+// token's own but it can be widened to a whole construct.
+#[derive(Debug, Clone)]
+pub enum LoxError {
+    Scan {
+        span: Span,
+        message: String,
+        notes: Vec<Note>,
+    },
+    // The token is boxed to keep results carrying an error small
+    Parse {
+        token: Box<Token>,
+        span: Span,
+        message: String,
+        notes: Vec<Note>,
+    },
+    Resolve {
+        token: Box<Token>,
+        span: Span,
+        message: String,
+        notes: Vec<Note>,
+    },
+    // Limits of the bytecode format, e.g. too many locals in a function
+    Compile {
+        span: Span,
+        message: String,
+        notes: Vec<Note>,
+    },
+    Runtime {
+        span: Span,
+        message: String,
+        notes: Vec<Note>,
+    },
+    // The interpreter failed to write program output
+    Io {
+        message: String,
+    },
+    // A compiled .loxc file that is corrupt or from another format version
+    Load {
+        message: String,
+    },
+}
+
+// Extra context shown under an error, pointing at related source
+#[derive(Debug, Clone)]
+pub struct Note {
+    pub span: Span,
+    pub message: String,
+}
+
+impl LoxError {
+    pub fn scan(span: Span, message: &str) -> Self {
+        LoxError::Scan {
+            span,
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn parse(token: Token, message: &str) -> Self {
+        LoxError::Parse {
+            span: token.span,
+            token: Box::new(token),
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn resolve(token: Token, message: &str) -> Self {
+        LoxError::Resolve {
+            span: token.span,
+            token: Box::new(token),
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn compile(span: Span, message: &str) -> Self {
+        LoxError::Compile {
+            span,
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn runtime(token: Token, message: &str) -> Self {
+        Self::runtime_at(token.span, message)
+    }
+
+    // For the bytecode VM, which only knows the span of each instruction
+    pub fn runtime_at(span: Span, message: &str) -> Self {
+        LoxError::Runtime {
+            span,
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn io(message: &str) -> Self {
+        LoxError::Io {
+            message: message.to_string(),
+        }
+    }
+
+    pub fn load(message: &str) -> Self {
+        LoxError::Load {
+            message: message.to_string(),
+        }
+    }
+
+    // Points the error at `span` instead of its token
+    pub fn with_span(mut self, new_span: Span) -> Self {
+        match &mut self {
+            LoxError::Scan { span, .. }
+            | LoxError::Parse { span, .. }
+            | LoxError::Resolve { span, .. }
+            | LoxError::Compile { span, .. }
+            | LoxError::Runtime { span, .. } => *span = new_span,
+            LoxError::Io { .. } | LoxError::Load { .. } => (),
+        }
+        self
+    }
+
+    pub fn with_note(mut self, note_span: Span, note: &str) -> Self {
+        match &mut self {
+            LoxError::Scan { notes, .. }
+            | LoxError::Parse { notes, .. }
+            | LoxError::Resolve { notes, .. }
+            | LoxError::Compile { notes, .. }
+            | LoxError::Runtime { notes, .. } => notes.push(Note {
+                span: note_span,
+                message: note.to_string(),
+            }),
+            LoxError::Io { .. } | LoxError::Load { .. } => (),
+        }
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            LoxError::Scan { message, .. }
+            | LoxError::Parse { message, .. }
+            | LoxError::Resolve { message, .. }
+            | LoxError::Compile { message, .. }
+            | LoxError::Runtime { message, .. }
+            | LoxError::Io { message }
+            | LoxError::Load { message } => message,
+        }
+    }
+
+    pub fn notes(&self) -> &[Note] {
+        match self {
+            LoxError::Scan { notes, .. }
+            | LoxError::Parse { notes, .. }
+            | LoxError::Resolve { notes, .. }
+            | LoxError::Compile { notes, .. }
+            | LoxError::Runtime { notes, .. } => notes,
+            LoxError::Io { .. } | LoxError::Load { .. } => &[],
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            LoxError::Scan { span, .. }
+            | LoxError::Parse { span, .. }
+            | LoxError::Resolve { span, .. }
+            | LoxError::Compile { span, .. }
+            | LoxError::Runtime { span, .. } => Some(*span),
+            LoxError::Io { .. } | LoxError::Load { .. } => None,
+        }
+    }
+
+    // Errors raised while the program runs, as opposed to before it starts
+    pub fn is_runtime(&self) -> bool {
+        matches!(self, LoxError::Runtime { .. } | LoxError::Io { .. })
+    }
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Scan { span, message, .. } | LoxError::Compile { span, message, .. } => {
+                write!(f, "[line {}:{}] Error: {}", span.line, span.column, message)
+            }
+            // Located at the token the message names, the span may be wider
+            LoxError::Parse { token, message, .. } | LoxError::Resolve { token, message, .. } => {
+                let location: Span = token.span;
+                if token.token_type == TokenType::EOF {
+                    write!(
+                        f,
+                        "[line {}:{}] Error at end: {}",
+                        location.line, location.column, message
+                    )
+                } else {
+                    write!(
+                        f,
+                        "[line {}:{}] Error at '{}': {}",
+                        location.line,
+                        location.column,
+                        token.text(),
+                        message
+                    )
+                }
+            }
+            LoxError::Runtime { span, message, .. } => {
+                write!(f, "{}\n[line {}:{}]", message, span.line, span.column)
+            }
+            LoxError::Io { message } => write!(f, "{}", message),
+            LoxError::Load { message } => write!(f, "Error: {}", message),
+        }
+    }
+}
@@ -1,5 +1,9 @@
-use crate::token::Token;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::token::{Span, Token};
+
+// Every node carries the span of source it was parsed from
 #[derive(Debug, Clone)]
 pub enum Expr {
     Binary {
@@ -7,54 +11,103 @@ pub enum Expr {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
+        span: Span,
+    },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+        span: Span,
     },
     Unary {
         operator: Token,
         right: Box<Expr>,
+        span: Span,
     },
     Literal {
-        // Temp solution for representing string as null
         literal: LiteralRepresentations,
+        span: Span,
     },
     Grouping {
         expr: Box<Expr>,
+        span: Span,
     },
-    // Temp solution for handling end scenario of primary
-    FailScenario {
-        reason: String,
+    Call {
+        callee: Box<Expr>,
+        // Closing paren, kept for its location when reporting call errors
+        paren: Token,
+        arguments: Vec<Expr>,
+        span: Span,
     },
     Variable {
+        // Identifies the expression when the resolver records its scope depth
+        id: usize,
+        name: Token,
+        span: Span,
+    },
+    Assign {
+        id: usize,
         name: Token,
+        value: Box<Expr>,
+        span: Span,
+    },
+    Get {
+        object: Box<Expr>,
+        name: Token,
+        span: Span,
+    },
+    Set {
+        object: Box<Expr>,
+        name: Token,
+        value: Box<Expr>,
+        span: Span,
+    },
+    This {
+        id: usize,
+        keyword: Token,
+        span: Span,
+    },
+    Super {
+        id: usize,
+        keyword: Token,
+        method: Token,
+        span: Span,
     },
 }
 
-#[derive(Debug, Clone)]
-pub struct Literal {
-    pub literal: LiteralRepresentations,
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Binary { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Literal { span, .. }
+            | Expr::Grouping { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Get { span, .. }
+            | Expr::Set { span, .. }
+            | Expr::This { span, .. }
+            | Expr::Super { span, .. } => *span,
+        }
+    }
+}
+
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Hands out ids that are unique across every parse in the process, so an
+// interpreter outliving one parser never mixes up resolved expressions.
+pub fn next_expr_id() -> usize {
+    NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
 }
 
-// Represent Lox Nil type as a custom Null type which will be a string, temp solution
-type Null = String;
+// Literal values as written in the source, evaluated into a runtime Value
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum LiteralRepresentations {
     CustomBoolean { val: bool },
-    CustomNil { val: Null },
+    CustomNil,
     CustomNumber { val: f64 },
-    CustomString { val: String },
-}
-
-pub struct CustomBoolean {
-    val: bool,
-}
-
-pub struct CustomNil {
-    val: Null,
-}
-
-pub struct CustomNumber {
-    val: f64,
-}
-
-pub struct CustomString {
-    val: String,
+    CustomString { val: Rc<str> },
 }
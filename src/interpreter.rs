@@ -1,571 +1,618 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+
 use crate::environment::Environment;
-use crate::expr::{self, CustomBoolean, CustomNumber, Expr, Literal, LiteralRepresentations};
-use crate::interpreter_objects::InterpretedParsed;
-use crate::stmt::{Expression, Print, Stmt, Var};
-use crate::token::Token;
+use crate::error::LoxError;
+use crate::expr::{Expr, LiteralRepresentations};
+use crate::interpreter_objects::{instance_get, LoxClass, LoxFunction, LoxInstance};
+use crate::native::{self, NativeArgs, NativeFunction, NativeResult};
+use crate::stmt::{Block, Class, Expression, If, Print, Return, Stmt, Var, While};
+use crate::symbol::Symbol;
+use crate::token::{Span, Token};
 use crate::token_type::TokenType;
+use crate::value::Value;
+
+// Same limit as the VM's, so runaway recursion is a Lox error on both
+// backends rather than overflowing the Rust stack
+const MAX_CALL_DEPTH: usize = 1024;
 
 pub struct Interpreter {
-    environment: Environment,
+    pub globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Scope distance of each resolved local, keyed by expression id
+    locals: HashMap<usize, usize>,
+    // Lox calls in progress
+    call_depth: usize,
+    // Where `print` writes, stdout unless the embedder asks for something else
+    output: Box<dyn Write>,
 }
 
+// Unwinds statement execution, either because evaluation failed or because a
+// `return` is carrying its value back out to the function call.
 #[derive(Debug)]
-pub struct InterpreterError {
-    reason: String,
+enum Unwind {
+    Error(LoxError),
+    Return(Value),
 }
 
-// TODO: Re-implement error structs better
-pub struct RuntimeError {
-    pub reason: String,
-    pub token: Token,
+impl From<LoxError> for Unwind {
+    fn from(err: LoxError) -> Self {
+        Unwind::Error(err)
+    }
 }
 
-type RLoxEvalResult = Result<Expr, InterpreterError>;
+type RLoxEvalResult = Result<Value, LoxError>;
+type RLoxExecResult = Result<(), Unwind>;
 
 impl Interpreter {
     pub fn new() -> Self {
-        Self {
-            environment: Environment::new(),
-        }
+        Self::with_output(Box::new(io::stdout()))
     }
 
-    pub fn interpret_stmts(&mut self, statements: Vec<Stmt>) -> Result<(), &str> {
-        // TODO: Need to fix this
-        for expr in statements {
-            let x = self.eval_stmt(expr);
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let globals: Rc<RefCell<Environment>> = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter: Interpreter = Self {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: HashMap::new(),
+            call_depth: 0,
+            output,
+        };
+        interpreter.define_native("clock", 0, native::clock);
+        interpreter
+    }
 
-            println!("{:#?}", x.unwrap());
-        }
-        Ok(())
+    // Makes a Rust function callable from Lox as a global. Arity is checked
+    // before the body runs and an Err from it becomes a runtime error.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&NativeArgs) -> NativeResult + 'static,
+    {
+        let native: Value = Value::Native(Rc::new(NativeFunction::new(name, arity, body)));
+        self.globals
+            .borrow_mut()
+            .define(Symbol::intern(name), native);
     }
 
-    pub fn interpret(&mut self, expr: Expr) -> RLoxEvalResult {
-        return self.eval(expr);
+    // Called by the resolver for every local variable reference, globals are
+    // left out and looked up dynamically.
+    pub fn resolve(&mut self, id: usize, depth: usize) {
+        self.locals.insert(id, depth);
     }
 
-    fn eval_stmt(&mut self, expr: Stmt) -> RLoxEvalResult {
+    // Runs a program. Its value is that of the final statement when that is
+    // an expression statement, so embedders can evaluate `1 + 2;`, and nil
+    // otherwise.
+    pub fn interpret_stmts(&mut self, statements: &[Stmt]) -> RLoxEvalResult {
+        let count: usize = statements.len();
+        for (index, stmt) in statements.iter().enumerate() {
+            if index + 1 == count {
+                if let Stmt::Expression(Expression { expression, .. }) = stmt {
+                    return self.eval(expression);
+                }
+            }
+
+            match self.eval_stmt(stmt) {
+                Ok(_) => (),
+                Err(Unwind::Error(e)) => return Err(e),
+                // A return outside of any function ends the script
+                Err(Unwind::Return(_)) => break,
+            }
+        }
+        Ok(Value::Nil)
+    }
+
+    fn eval_stmt(&mut self, expr: &Stmt) -> RLoxExecResult {
         match expr {
-            Stmt::Expression(Expression { expression: expr }) => {
-                return self.eval(expr);
+            Stmt::Expression(Expression {
+                expression: expr, ..
+            }) => {
+                self.eval(expr)?;
+                return Ok(());
             }
             Stmt::Print(Print {
                 print_expression: expr,
+                ..
             }) => {
-                return self.eval(expr);
+                let value: Value = self.eval(expr)?;
+                writeln!(self.output, "{}", value)
+                    .map_err(|e| LoxError::io(&format!("Failed to write output: {}", e)))?;
+                return Ok(());
             }
-            Stmt::Var(Var { name, initializer }) => match initializer {
-                Some(initializer) => {
-                    let expr = self.eval(initializer)?;
-
-                    match self.parse_expr(&expr) {
-                        Ok(value) => {
-                            self.environment.define(name.lexeme, value);
-                            return Ok(expr);
-                        }
-                        Err(_) => {
-                            return Err(InterpreterError {
-                                reason: format!(
-                                    "Failed to fetch literal from expression {:#?}",
-                                    expr
-                                ),
-                            })
-                        }
-                    }
+            Stmt::If(If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            }) => {
+                if self.eval(condition)?.is_truthy() {
+                    return self.eval_stmt(then_branch);
                 }
-                None => {
-                    let null_expr: Expr = Expr::Literal {
-                        literal: LiteralRepresentations::CustomNil {
-                            val: "Null".to_string(),
-                        },
-                    };
-                    self.environment.define(
-                        name.lexeme,
-                        LiteralRepresentations::CustomNil {
-                            val: "Null".to_string(),
-                        },
+                match else_branch {
+                    Some(else_branch) => return self.eval_stmt(else_branch),
+                    None => return Ok(()),
+                }
+            }
+            Stmt::While(While {
+                condition, body, ..
+            }) => {
+                while self.eval(condition)?.is_truthy() {
+                    self.eval_stmt(body)?;
+                }
+                return Ok(());
+            }
+            Stmt::Block(Block { statements, .. }) => {
+                let enclosed: Environment = Environment::new_enclosed(Rc::clone(&self.environment));
+                return self.execute_block(statements, enclosed);
+            }
+            Stmt::Function(declaration) => {
+                let name: Symbol = declaration.name.lexeme;
+                let function: Value = Value::Function(Rc::new(LoxFunction::new(
+                    Rc::clone(declaration),
+                    Rc::clone(&self.environment),
+                    false,
+                )));
+                self.environment.borrow_mut().define(name, function);
+                return Ok(());
+            }
+            Stmt::Class(Class {
+                name,
+                superclass,
+                methods,
+                ..
+            }) => {
+                let superclass: Option<Rc<LoxClass>> = match superclass {
+                    Some(superclass) => Some(self.eval_superclass(superclass, name)?),
+                    None => None,
+                };
+
+                // Methods of a subclass close over a scope that defines `super`
+                let mut method_scope: Rc<RefCell<Environment>> = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    let mut scope: Environment = Environment::new_enclosed(method_scope);
+                    scope.define(Symbol::super_(), Value::Class(Rc::clone(superclass)));
+                    method_scope = Rc::new(RefCell::new(scope));
+                }
+
+                let mut class_methods: HashMap<Symbol, Rc<LoxFunction>> = HashMap::new();
+                for method in methods {
+                    let is_initializer: bool = method.name.lexeme == Symbol::init();
+                    class_methods.insert(
+                        method.name.lexeme,
+                        Rc::new(LoxFunction::new(
+                            Rc::clone(method),
+                            Rc::clone(&method_scope),
+                            is_initializer,
+                        )),
                     );
-                    return Ok(null_expr);
                 }
-            },
+
+                let class: Value = Value::Class(Rc::new(LoxClass::new(
+                    name.lexeme.to_string(),
+                    superclass,
+                    class_methods,
+                )));
+                self.environment.borrow_mut().define(name.lexeme, class);
+                return Ok(());
+            }
+            Stmt::Return(Return { value, .. }) => {
+                let value: Value = match value {
+                    Some(value) => self.eval(value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Stmt::Var(Var {
+                name, initializer, ..
+            }) => {
+                let value: Value = match initializer {
+                    Some(initializer) => self.eval(initializer)?,
+                    None => Value::Nil,
+                };
+                self.environment.borrow_mut().define(name.lexeme, value);
+                return Ok(());
+            }
         }
     }
 
-    fn eval(&mut self, expr: Expr) -> RLoxEvalResult {
+    // Runs the statements in the given scope. The previous scope is restored
+    // before returning, also when a statement fails.
+    fn execute_block(&mut self, statements: &[Stmt], scope: Environment) -> RLoxExecResult {
+        let previous: Rc<RefCell<Environment>> =
+            std::mem::replace(&mut self.environment, Rc::new(RefCell::new(scope)));
+
+        let mut result: RLoxExecResult = Ok(());
+        for stmt in statements {
+            if let Err(e) = self.eval_stmt(stmt) {
+                result = Err(e);
+                break;
+            }
+        }
+
+        self.environment = previous;
+        result
+    }
+
+    fn eval(&mut self, expr: &Expr) -> RLoxEvalResult {
         match expr {
             Expr::Binary {
                 left,
                 operator,
                 right,
-            } => self.eval_binary(*left, operator, *right),
-
-            Expr::Literal { literal } => match literal {
-                LiteralRepresentations::CustomBoolean { val } => {
-                    return Ok(Expr::Literal {
-                        literal: LiteralRepresentations::CustomBoolean { val },
-                    })
-                }
-                LiteralRepresentations::CustomNil { val } => {
-                    return Ok(Expr::Literal {
-                        literal: LiteralRepresentations::CustomNil { val },
-                    })
-                }
-                LiteralRepresentations::CustomNumber { val } => {
-                    return Ok(Expr::Literal {
-                        literal: LiteralRepresentations::CustomNumber { val },
-                    })
-                }
-                LiteralRepresentations::CustomString { val } => {
-                    return Ok(Expr::Literal {
-                        literal: LiteralRepresentations::CustomString { val },
-                    })
-                }
+                span,
+            } => self.eval_binary(left, operator.clone(), right, *span),
+
+            Expr::Literal { literal, .. } => match literal {
+                LiteralRepresentations::CustomBoolean { val } => Ok(Value::Bool(*val)),
+                LiteralRepresentations::CustomNil => Ok(Value::Nil),
+                LiteralRepresentations::CustomNumber { val } => Ok(Value::Number(*val)),
+                LiteralRepresentations::CustomString { val } => Ok(Value::Str(Rc::clone(val))),
             },
 
-            Expr::Grouping { expr } => self.eval(*expr),
-            Expr::Unary { operator, right } => self.eval_unary(operator, *right),
-            Expr::FailScenario { reason } => self.eval_fail_scenario(reason),
-            Expr::Variable { name } => {
-                let literal_value: LiteralRepresentations = self.environment.get(name).unwrap();
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => self.eval_logical(left, operator.clone(), right),
+            Expr::Grouping { expr, .. } => self.eval(expr),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                span,
+            } => self.eval_call(callee, paren.clone(), arguments, *span),
+            Expr::Unary {
+                operator, right, ..
+            } => self.eval_unary(operator.clone(), right),
+            Expr::Assign {
+                id, name, value, ..
+            } => {
+                let value: Value = self.eval(value)?;
+                let assigned = match self.locals.get(id) {
+                    Some(distance) => self.environment.borrow_mut().assign_at(
+                        *distance,
+                        name.clone(),
+                        value.clone(),
+                    ),
+                    None => self
+                        .globals
+                        .borrow_mut()
+                        .assign(name.clone(), value.clone()),
+                };
+                assigned?;
 
-                Ok(self.convert_literal_to_expr(literal_value))
+                Ok(value)
             }
+            Expr::Get { object, name, .. } => match self.eval(object)? {
+                Value::Instance(instance) => match instance_get(&instance, name) {
+                    Some(value) => Ok(value),
+                    None => {
+                        let message: String = format!("Undefined property '{}'.", name.lexeme);
+                        Err(LoxError::runtime(name.clone(), &message))
+                    }
+                },
+                _ => Err(LoxError::runtime(
+                    name.clone(),
+                    "Only instances have properties.",
+                )),
+            },
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                let instance: Rc<RefCell<LoxInstance>> = match self.eval(object)? {
+                    Value::Instance(instance) => instance,
+                    _ => {
+                        return Err(LoxError::runtime(
+                            name.clone(),
+                            "Only instances have fields.",
+                        ))
+                    }
+                };
+
+                let value: Value = self.eval(value)?;
+                instance.borrow_mut().set(name.clone(), value.clone());
+                Ok(value)
+            }
+            Expr::This { id, keyword, .. } => self.look_up_variable(*id, keyword.clone()),
+            Expr::Super {
+                id,
+                keyword,
+                method,
+                ..
+            } => self.eval_super(*id, keyword.clone(), method.clone()),
+            Expr::Variable { id, name, .. } => self.look_up_variable(*id, name.clone()),
         }
     }
 
-    fn eval_binary(&mut self, left: Expr, operator: Token, right: Expr) -> RLoxEvalResult {
-        let left: RLoxEvalResult = self.eval(left);
-        let right: RLoxEvalResult = self.eval(right);
-
-        let left_expr = left.unwrap();
-        let right_expr = right.unwrap();
-
-        let x: (InterpretedParsed, InterpretedParsed) =
-            self.binary_evaluation(&left_expr, &right_expr)?;
+    fn eval_binary(
+        &mut self,
+        left: &Expr,
+        operator: Token,
+        right: &Expr,
+        span: Span,
+    ) -> RLoxEvalResult {
+        let left: Value = self.eval(left)?;
+        let right: Value = self.eval(right)?;
 
         match operator.token_type {
-            TokenType::BANGEQUAL => {
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean {
-                        val: !self.is_equal(left_expr, right_expr),
-                    },
-                })
-            }
+            TokenType::BANGEQUAL => return Ok(Value::Bool(left != right)),
 
-            TokenType::EQUALEQUAL => {
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean {
-                        val: self.is_equal(left_expr, right_expr),
-                    },
-                })
-            }
+            TokenType::EQUALEQUAL => return Ok(Value::Bool(left == right)),
 
             TokenType::GREATER => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num: f64 = self.fetch_numeric_value(x.0)?;
-                let right_num: f64 = self.fetch_numeric_value(x.1)?;
-
-                return self.fetch_boolean_evaluation(TokenType::GREATER, left_num, right_num);
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Bool(left_num > right_num));
             }
 
             TokenType::GREATEREQUAL => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num: f64 = self.fetch_numeric_value(x.0)?;
-                let right_num: f64 = self.fetch_numeric_value(x.1)?;
-
-                return self.fetch_boolean_evaluation(TokenType::GREATEREQUAL, left_num, right_num);
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Bool(left_num >= right_num));
             }
 
             TokenType::LESS => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num: f64 = self.fetch_numeric_value(x.0)?;
-                let right_num: f64 = self.fetch_numeric_value(x.1)?;
-
-                return self.fetch_boolean_evaluation(TokenType::LESS, left_num, right_num);
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Bool(left_num < right_num));
             }
 
             TokenType::LESSEQUAL => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num: f64 = self.fetch_numeric_value(x.0)?;
-                let right_num: f64 = self.fetch_numeric_value(x.1)?;
-
-                return self.fetch_boolean_evaluation(TokenType::LESSEQUAL, left_num, right_num);
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Bool(left_num <= right_num));
             }
 
             TokenType::MINUS => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num = self.fetch_numeric_value(x.0)?;
-                let right_num = self.fetch_numeric_value(x.1)?;
-
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber {
-                        val: left_num - right_num,
-                    },
-                });
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Number(left_num - right_num));
             }
 
-            TokenType::PLUS => {
-                match (left_expr, right_expr) {
-                    (
-                        Expr::Literal {
-                            literal: LiteralRepresentations::CustomNumber { val: _l },
-                        },
-                        Expr::Literal {
-                            literal: LiteralRepresentations::CustomNumber { val: _r },
-                        },
-                    ) => {
-                        let left_num = self.fetch_numeric_value(x.0)?;
-                        let right_num = self.fetch_numeric_value(x.1)?;
-
-                        return Ok(Expr::Literal {
-                            literal: LiteralRepresentations::CustomNumber {
-                                val: left_num + right_num,
-                            },
-                        });
-                    }
-
-                    (
-                        Expr::Literal {
-                            literal: LiteralRepresentations::CustomString { val: _l },
-                        },
-                        Expr::Literal {
-                            literal: LiteralRepresentations::CustomString { val: _r },
-                        },
-                    ) => {
-                        let left_str = self.fetch_stringified_value(x.0)?;
-                        let right_str = self.fetch_stringified_value(x.1)?;
-
-                        return Ok(Expr::Literal {
-                            literal: LiteralRepresentations::CustomString {
-                                val: format!("{}{}", left_str, right_str),
-                            },
-                        });
-                    }
-                    _ => {
-                        return Err(InterpreterError {
-                            reason: "PLUS operation failed, can only add 2 numbers or 2 strings"
-                                .to_string(),
-                        });
-                    }
-                };
-            }
+            TokenType::PLUS => match (left, right) {
+                (Value::Number(left_num), Value::Number(right_num)) => {
+                    return Ok(Value::Number(left_num + right_num));
+                }
+                (Value::Str(left_str), Value::Str(right_str)) => {
+                    let joined: String = format!("{}{}", left_str, right_str);
+                    return Ok(Value::Str(joined.into()));
+                }
+                _ => {
+                    return Err(LoxError::runtime(
+                        operator,
+                        "Operands must be two numbers or two strings.",
+                    )
+                    .with_span(span));
+                }
+            },
 
             TokenType::SLASH => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num = self.fetch_numeric_value(x.0)?;
-                let right_num = self.fetch_numeric_value(x.1)?;
-
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber {
-                        val: left_num / right_num,
-                    },
-                });
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Number(left_num / right_num));
             }
 
             TokenType::STAR => {
-                self.check_number_operands(operator, left_expr, right_expr)?;
-                let left_num = self.fetch_numeric_value(x.0)?;
-                let right_num = self.fetch_numeric_value(x.1)?;
-
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber {
-                        val: left_num * right_num,
-                    },
-                });
+                let (left_num, right_num) =
+                    self.check_number_operands(&operator, span, left, right)?;
+                return Ok(Value::Number(left_num * right_num));
             }
 
-            _ => Err(InterpreterError {
-                reason: "Fail".to_string(),
-            }),
+            _ => {
+                let message: String = format!("Unknown binary operator '{}'.", operator.lexeme);
+                Err(LoxError::runtime(operator, &message))
+            }
         }
     }
 
-    fn binary_evaluation(
-        &self,
-        left: &Expr,
-        right: &Expr,
-    ) -> Result<(InterpretedParsed, InterpretedParsed), InterpreterError> {
-        let left_expr = left;
-        let right_expr = right;
-
-        let left_lit = self.parse_expr(left_expr)?;
-        let right_lit = self.parse_expr(right_expr)?;
-
-        return Ok((
-            self.get_val_from_literal(left_lit),
-            self.get_val_from_literal(right_lit),
-        ));
-    }
-
-    fn eval_unary(&mut self, operator: Token, right: Expr) -> RLoxEvalResult {
-        let right: RLoxEvalResult = self.eval(right);
+    fn eval_call(
+        &mut self,
+        callee: &Expr,
+        paren: Token,
+        arguments: &[Expr],
+        span: Span,
+    ) -> RLoxEvalResult {
+        let callee_span: Span = callee.span();
+        let callee: Value = self.eval(callee)?;
 
-        match operator.token_type {
-            TokenType::BANG => match right {
-                Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean { val },
-                }) => {
-                    return Ok(Expr::Literal {
-                        literal: LiteralRepresentations::CustomBoolean {
-                            val: self.is_truthy(Literal {
-                                literal: LiteralRepresentations::CustomBoolean { val: !val },
-                            }),
-                        },
-                    });
-                }
-                _ => Err(InterpreterError {
-                    reason: "Only accepts boolean types".to_string(),
-                }),
-            },
-            TokenType::MINUS => match right {
-                Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber { val: number },
-                }) => {
-                    self.check_number_operand(operator, right.unwrap())?;
-
-                    return Ok(Expr::Literal {
-                        literal: LiteralRepresentations::CustomNumber { val: -number },
-                    });
-                }
-                _ => Err(InterpreterError {
-                    reason: "Could not parse expression in unary evaluation".to_string(),
-                }),
-            },
-            _ => Err(InterpreterError {
-                reason: "Failed to interpret, unary method accepts only BANG and MINUS type"
-                    .to_string(),
-            }),
+        let mut evaluated_arguments: Vec<Value> = Vec::new();
+        for argument in arguments {
+            evaluated_arguments.push(self.eval(argument)?);
         }
-    }
 
-    // Utilities
+        let arity: usize = match &callee {
+            Value::Function(function) => function.arity(),
+            Value::Native(native) => native.arity,
+            Value::Class(class) => class.arity(),
+            _ => {
+                return Err(
+                    LoxError::runtime(paren, "Can only call functions and classes.")
+                        .with_span(callee_span),
+                )
+            }
+        };
+
+        if evaluated_arguments.len() != arity {
+            let message: String = format!(
+                "Expected {} arguments but got {}.",
+                arity,
+                evaluated_arguments.len()
+            );
+            return Err(LoxError::runtime(paren, &message).with_span(span));
+        }
 
-    fn check_number_operand(&self, operator: Token, operand: Expr) -> Result<(), InterpreterError> {
-        match operand {
-            Expr::Literal {
-                literal: LiteralRepresentations::CustomNumber { val: _ },
-            } => Ok(()),
-            _ => Err(InterpreterError {
-                reason: format!("Operand must be a number, operator is {:#?}", operator),
-            }),
+        // The top level takes up one frame, as the script does in the VM
+        if self.call_depth + 1 == MAX_CALL_DEPTH {
+            return Err(LoxError::runtime(paren, "Stack overflow.").with_span(span));
         }
+        self.call_depth += 1;
+        let result: RLoxEvalResult = match callee {
+            Value::Class(class) => self.instantiate(class, evaluated_arguments),
+            Value::Function(function) => self.call_function(&function, evaluated_arguments),
+            Value::Native(native) => native
+                .call(&evaluated_arguments)
+                .map_err(|message| LoxError::runtime(paren, &message).with_span(span)),
+            _ => unreachable!("callee checked above"),
+        };
+        self.call_depth -= 1;
+        result
     }
 
-    fn check_number_operands(
-        &self,
-        operator: Token,
-        left: Expr,
-        right: Expr,
-    ) -> Result<(), InterpreterError> {
-        match (left, right) {
-            (
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber { val: _ },
-                },
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber { val: _ },
-                },
-            ) => Ok(()),
-            _ => Err(InterpreterError {
-                reason: format!("Operands must be numbers, operator is {:#?}", operator),
-            }),
+    // The error points at the superclass name, falling back to the class
+    // being declared.
+    fn eval_superclass(
+        &mut self,
+        superclass: &Expr,
+        class_name: &Token,
+    ) -> Result<Rc<LoxClass>, LoxError> {
+        let token: Token = match superclass {
+            Expr::Variable { name, .. } => name.clone(),
+            _ => class_name.clone(),
+        };
+
+        let span: Span = superclass.span();
+        match self.eval(superclass)? {
+            Value::Class(class) => Ok(class),
+            _ => Err(LoxError::runtime(token, "Superclass must be a class.").with_span(span)),
         }
     }
 
-    fn convert_literal_to_expr(&self, literal: LiteralRepresentations) -> Expr {
-        match literal {
-            LiteralRepresentations::CustomBoolean { val } => {
-                return Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean { val: val },
-                }
-            }
-            LiteralRepresentations::CustomNil { val } => {
-                return Expr::Literal {
-                    literal: LiteralRepresentations::CustomNil { val: val },
-                }
-            }
-            LiteralRepresentations::CustomNumber { val } => {
-                return Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber { val: val },
-                }
-            }
-            LiteralRepresentations::CustomString { val } => {
-                return Expr::Literal {
-                    literal: LiteralRepresentations::CustomString { val: val },
-                }
+    // `super` sits in the scope right outside the one binding `this`, so the
+    // instance is found one step closer than the resolved distance.
+    fn eval_super(&mut self, id: usize, keyword: Token, method: Token) -> RLoxEvalResult {
+        let distance: usize = match self.locals.get(&id) {
+            Some(distance) => *distance,
+            None => return Err(LoxError::runtime(keyword, "Unresolved 'super'.")),
+        };
+
+        let this: Token = Token::new(TokenType::THIS, Symbol::this(), None, keyword.span);
+        let superclass: Value = self.environment.borrow().get_at(distance, keyword)?;
+        let object: Value = self.environment.borrow().get_at(distance - 1, this)?;
+
+        let (superclass, instance) = match (superclass, object) {
+            (Value::Class(superclass), Value::Instance(instance)) => (superclass, instance),
+            _ => return Err(LoxError::runtime(method, "Invalid 'super' binding.")),
+        };
+
+        match superclass.find_method(method.lexeme) {
+            Some(found) => Ok(Value::Function(Rc::new(found.bind(instance)))),
+            None => {
+                let message: String = format!("Undefined property '{}'.", method.lexeme);
+                Err(LoxError::runtime(method, &message))
             }
         }
     }
 
-    fn fetch_numeric_value(
-        &self,
-        interpreted_value: InterpretedParsed,
-    ) -> Result<f64, InterpreterError> {
-        if let InterpretedParsed::IntepretedNum { value } = interpreted_value {
-            return Ok(value);
-        } else {
-            return Err(InterpreterError {
-                reason: "Failed to fetch numeric value".to_string(),
-            });
-        }
-    }
+    // Calling a class creates an instance and runs `init` on it if present
+    fn instantiate(&mut self, class: Rc<LoxClass>, arguments: Vec<Value>) -> RLoxEvalResult {
+        let instance: Rc<RefCell<LoxInstance>> =
+            Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class))));
 
-    fn fetch_stringified_value(
-        &self,
-        interpreted_value: InterpretedParsed,
-    ) -> Result<String, InterpreterError> {
-        if let InterpretedParsed::InterpretedStr { value } = interpreted_value {
-            return Ok(value);
-        } else {
-            return Err(InterpreterError {
-                reason: "Failed to fetch String".to_string(),
-            });
+        if let Some(initializer) = class.find_method(Symbol::init()) {
+            self.call_function(&initializer.bind(Rc::clone(&instance)), arguments)?;
         }
-    }
 
-    fn fetch_boolean_evaluation(
-        &self,
-        operator_token_type: TokenType,
-        left_num: f64,
-        right_num: f64,
-    ) -> RLoxEvalResult {
-        match operator_token_type {
-            TokenType::GREATER => {
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean {
-                        val: left_num > right_num,
-                    },
-                })
-            }
+        Ok(Value::Instance(instance))
+    }
 
-            TokenType::GREATEREQUAL => {
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean {
-                        val: left_num >= right_num,
-                    },
-                })
-            }
+    fn call_function(&mut self, function: &LoxFunction, arguments: Vec<Value>) -> RLoxEvalResult {
+        // Parameters live in a fresh scope nested in the one the function was
+        // declared in, so the body sees the variables it closed over.
+        let mut scope: Environment = Environment::new_enclosed(Rc::clone(&function.closure));
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            scope.define(param.lexeme, argument);
+        }
 
-            TokenType::LESS => {
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean {
-                        val: left_num < right_num,
-                    },
-                })
+        let returned: Value = match self.execute_block(&function.declaration.body, scope) {
+            Ok(_) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(e)) => return Err(e),
+        };
+
+        if function.is_initializer {
+            let this: Option<Value> = function
+                .closure
+                .borrow()
+                .values
+                .get(&Symbol::this())
+                .cloned();
+            if let Some(this) = this {
+                return Ok(this);
             }
+        }
+        Ok(returned)
+    }
 
-            TokenType::LESSEQUAL => {
-                return Ok(Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean {
-                        val: left_num <= right_num,
-                    },
-                })
-            }
+    // Short-circuits and evaluates to the operand that decided the result,
+    // not to a coerced boolean, e.g. `nil or "default"` gives "default".
+    fn eval_logical(&mut self, left: &Expr, operator: Token, right: &Expr) -> RLoxEvalResult {
+        let left: Value = self.eval(left)?;
 
-            _ => {
-                return Err(InterpreterError {
-                    reason: "Failed, only expecting boolean comparisions".to_string(),
-                })
+        if operator.token_type == TokenType::OR {
+            if left.is_truthy() {
+                return Ok(left);
             }
+        } else if !left.is_truthy() {
+            return Ok(left);
         }
-    }
 
-    fn is_equal(&self, left: Expr, right: Expr) -> bool {
-        match (left, right) {
-            (
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomNil { val: _ },
-                },
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomNil { val: _ },
-                },
-            ) => return true,
-            (
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomString { val: l },
-                },
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomString { val: r },
-                },
-            ) => return l == r,
-            (
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean { val: l },
-                },
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomBoolean { val: r },
-                },
-            ) => return l == r,
-            (
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber { val: l },
-                },
-                Expr::Literal {
-                    literal: LiteralRepresentations::CustomNumber { val: r },
-                },
-            ) => return l == r,
-            _ => return false,
-        }
+        self.eval(right)
     }
 
-    fn get_val_from_literal(&self, l: LiteralRepresentations) -> InterpretedParsed {
-        match l {
-            LiteralRepresentations::CustomNumber { val } => {
-                InterpretedParsed::IntepretedNum { value: val }
-            }
-            LiteralRepresentations::CustomBoolean { val } => {
-                InterpretedParsed::InterpretedBool { value: val }
+    fn eval_unary(&mut self, operator: Token, right: &Expr) -> RLoxEvalResult {
+        let right: Value = self.eval(right)?;
+
+        match operator.token_type {
+            TokenType::BANG => Ok(Value::Bool(!right.is_truthy())),
+            TokenType::MINUS => {
+                let number: f64 = self.check_number_operand(&operator, right)?;
+                Ok(Value::Number(-number))
             }
-            LiteralRepresentations::CustomString { val } => {
-                InterpretedParsed::InterpretedStr { value: val }
+            _ => {
+                let message: String = format!("Unknown unary operator '{}'.", operator.lexeme);
+                Err(LoxError::runtime(operator, &message))
             }
-            _ => panic!(),
         }
     }
 
-    fn parse_expr(&self, expr: &Expr) -> Result<LiteralRepresentations, InterpreterError> {
-        match expr {
-            Expr::Literal { literal } => match literal {
-                LiteralRepresentations::CustomBoolean { val } => {
-                    Ok(LiteralRepresentations::CustomBoolean { val: *val })
-                }
-                LiteralRepresentations::CustomNil { val: _ } => {
-                    Ok(LiteralRepresentations::CustomNil {
-                        val: "Fail".to_string(),
-                    })
-                }
-
-                LiteralRepresentations::CustomNumber { val } => {
-                    Ok(LiteralRepresentations::CustomNumber { val: *val })
-                }
-                LiteralRepresentations::CustomString { val } => {
-                    Ok(LiteralRepresentations::CustomString {
-                        val: String::from(val),
-                    })
-                }
+    // Utilities
 
-                _ => Err(InterpreterError {
-                    reason: "Incorrect literal representation reached".to_string(),
-                }),
-            },
-            _ => Err(InterpreterError {
-                reason: "Only parsing literals in this method.".to_string(),
-            }),
+    fn look_up_variable(&self, id: usize, name: Token) -> RLoxEvalResult {
+        match self.locals.get(&id) {
+            Some(distance) => self.environment.borrow().get_at(*distance, name),
+            None => self.globals.borrow().get(name),
         }
     }
 
-    fn is_truthy(&self, literal_expr: Literal) -> bool {
-        match literal_expr {
-            Literal {
-                literal: LiteralRepresentations::CustomNil { val: _ },
-            } => return false,
-            Literal {
-                literal: LiteralRepresentations::CustomBoolean { val },
-            } => return val,
-            _ => return true,
+    fn check_number_operand(&self, operator: &Token, operand: Value) -> Result<f64, LoxError> {
+        match operand {
+            Value::Number(val) => Ok(val),
+            _ => Err(LoxError::runtime(
+                operator.clone(),
+                "Operand must be a number.",
+            )),
         }
     }
 
-    fn eval_fail_scenario(&self, reason: String) -> RLoxEvalResult {
-        todo!()
+    // Errors point at the whole binary expression
+    fn check_number_operands(
+        &self,
+        operator: &Token,
+        span: Span,
+        left: Value,
+        right: Value,
+    ) -> Result<(f64, f64), LoxError> {
+        match (left, right) {
+            (Value::Number(left_num), Value::Number(right_num)) => Ok((left_num, right_num)),
+            _ => Err(
+                LoxError::runtime(operator.clone(), "Operands must be numbers.").with_span(span),
+            ),
+        }
     }
 }
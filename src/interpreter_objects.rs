@@ -1,17 +1,130 @@
-pub struct InterpretedNum {
-    pub value: f64,
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use crate::{environment::Environment, stmt::Function, symbol::Symbol, token::Token, value::Value};
+
+// Runtime representation of a declared `fun`, shared between all the places
+// holding the function value.
+pub struct LoxFunction {
+    pub declaration: Rc<Function>,
+    // Scope active where the function was declared
+    pub closure: Rc<RefCell<Environment>>,
+    // `init` methods always hand back the instance, whatever they return
+    pub is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<Function>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    // Creates the method as seen through one instance, with `this` defined in
+    // a scope wrapped around the method's closure.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment: Environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        environment.define(Symbol::this(), Value::Instance(instance));
+
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+}
+
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name.lexeme)
+    }
+}
+
+pub struct LoxClass {
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<Symbol, Rc<LoxFunction>>,
 }
 
-pub struct InterpretedBool {
-    pub value: bool,
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    // Methods of the class itself win over inherited ones
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        if let Some(method) = self.methods.get(&name) {
+            return Some(Rc::clone(method));
+        }
+
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+
+    // Calling the class forwards its arguments to `init`
+    pub fn arity(&self) -> usize {
+        match self.find_method(Symbol::init()) {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
 }
 
-pub struct InterpretedStr {
-    pub value: String,
+impl fmt::Debug for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<Symbol, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, name: Token, value: Value) {
+        self.fields.insert(name.lexeme, value);
+    }
+}
+
+// Fields shadow methods, and methods come back bound to the instance
+pub fn instance_get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Option<Value> {
+    if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+        return Some(value.clone());
+    }
+
+    let method: Rc<LoxFunction> = instance.borrow().class.find_method(name.lexeme)?;
+    Some(Value::Function(Rc::new(method.bind(Rc::clone(instance)))))
 }
 
-pub enum InterpretedParsed {
-    IntepretedNum { value: f64 },
-    InterpretedStr { value: String },
-    InterpretedBool { value: bool },
+impl fmt::Debug for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
 }
@@ -0,0 +1,63 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+//! A tree-walking interpreter for Lox, usable from Rust through [`Lox`], plus
+//! a bytecode compiler and stack [`Vm`] as a second backend.
+//!
+//! ```
+//! let mut lox = rlox::Lox::with_captured_output();
+//! lox.eval("var greeting = \"hi\"; print greeting;").unwrap();
+//! assert_eq!(lox.take_output(), "hi\n");
+//! ```
+//!
+//! Only the [`Vm`] has a garbage collector. The tree-walker shares
+//! environments and instances through reference counting, so closures and
+//! objects that end up referring to themselves are never freed. Embedders
+//! running long-lived or allocation-heavy scripts can use the [`Vm`], which
+//! has the same `eval` and `get_global`:
+//!
+//! ```
+//! let mut vm = rlox::Vm::new();
+//! vm.run("class Node {} var node = Node(); node.next = node;").unwrap();
+//! vm.eval("var label = \"looped\";").unwrap();
+//! assert_eq!(vm.eval("label + \"!\";").unwrap(), rlox::Value::from("looped!"));
+//! assert_eq!(vm.get_global("label"), Some(rlox::Value::from("looped")));
+//! ```
+
+mod bytecode_file;
+mod chunk;
+mod compiler;
+mod diagnostic;
+mod disassembler;
+mod environment;
+mod error;
+mod expr;
+mod interpreter;
+mod interpreter_objects;
+mod lox;
+mod native;
+mod object;
+mod parser;
+mod resolver;
+mod scanner;
+mod stmt;
+mod symbol;
+mod token;
+mod token_type;
+mod value;
+mod vm;
+
+pub use bytecode_file::{compile, is_compiled, FORMAT_VERSION};
+pub use diagnostic::Renderer;
+pub use disassembler::{disassemble, disassemble_compiled};
+pub use error::{LoxError, Note};
+pub use interpreter_objects::{LoxClass, LoxFunction, LoxInstance};
+pub use lox::Lox;
+pub use native::{FromValue, NativeArgs, NativeFunction, NativeResult};
+pub use object::GcStats;
+#[doc(hidden)]
+pub use scanner::scan;
+pub use symbol::Symbol;
+pub use token::{Span, Token};
+pub use token_type::TokenType;
+pub use value::Value;
+pub use vm::Vm;
@@ -1,100 +1,179 @@
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     fs,
-    io::{stdin, stdout, Write},
+    io::{self, Write},
+    path::Path,
+    rc::Rc,
 };
 
 use crate::{
-    expr::Expr,
-    interpreter::{self, Interpreter, RuntimeError},
+    error::LoxError,
+    interpreter::Interpreter,
+    native::{NativeArgs, NativeResult},
     parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
     stmt::Stmt,
+    symbol::Symbol,
     token::Token,
+    value::Value,
 };
-use crate::{scanner::Scanner, token_type::TokenType};
 
+// Handle for running Lox from Rust. Globals persist between calls, so a
+// script can be loaded once and then queried with further `eval`s.
+//
+// Lox calls recurse on the Rust stack. Past 1024 nested calls a script gets
+// a "Stack overflow." error, but getting that deep can take more stack than
+// a default thread has, the `rlox` binary runs on a 256 MB one.
 pub struct Lox {
-    pub had_error: bool,
-    pub had_runtime_error: bool,
+    interpreter: Interpreter,
+    // What `print` wrote, when the output is being captured
+    captured: Option<Rc<RefCell<Vec<u8>>>>,
+}
+
+// Write end of a capture buffer shared with the Lox handle
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 impl Lox {
+    // `print` goes to stdout
     pub fn new() -> Self {
         Self {
-            had_error: false,
-            had_runtime_error: false,
+            interpreter: Interpreter::new(),
+            captured: None,
         }
     }
 
-    pub fn repl(&mut self) {
-        loop {
-            print!("> ");
-            let _ = stdout().flush();
-            let mut input: String = String::new();
-            stdin()
-                .read_line(&mut input)
-                .expect("Err while reading input line");
-            let prettify_input: String = input.trim().to_string();
-            if prettify_input == ".exit" {
-                std::process::exit(1);
-            }
-            println!("input: {}", prettify_input);
-            self.run(prettify_input);
-            self.had_error = false;
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        Self {
+            interpreter: Interpreter::with_output(output),
+            captured: None,
         }
     }
 
-    pub fn run_file(&self, file_path: &String) {
-        let content: String = fs::read_to_string(file_path).expect("Err while reading file");
-        self.run(content);
-
-        if self.had_error {
-            std::process::exit(65);
+    // `print` is collected in memory, read it back with `take_output`
+    pub fn with_captured_output() -> Self {
+        let buffer: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+        Self {
+            interpreter: Interpreter::with_output(Box::new(Capture(Rc::clone(&buffer)))),
+            captured: Some(buffer),
         }
+    }
 
-        if self.had_runtime_error {
-            std::process::exit(70);
+    // Everything printed since the last call, empty when not capturing
+    pub fn take_output(&mut self) -> String {
+        match &self.captured {
+            Some(buffer) => {
+                let bytes: Vec<u8> = std::mem::take(&mut *buffer.borrow_mut());
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            None => String::new(),
         }
     }
 
-    pub fn run(&self, source: String) {
-        let mut scanner: Scanner = Scanner::new(source);
-        let tokens: &Vec<Token> = scanner.scan_tokens();
-        let mut parser: Parser = Parser::new(tokens.to_vec());
-        //let expr: Expr = parser.parse();
-        let statements: Vec<Stmt> = parser.parse_stmts();
+    // Runs the source and gives back the value of its final expression
+    // statement, or the first error found.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        self.run(source).map_err(|mut errors| errors.remove(0))
+    }
 
-        if self.had_error {
-            std::process::exit(65);
+    // Like `eval`, but with every error a phase found. Running stops after
+    // the first phase that reports any.
+    pub fn run(&mut self, source: &str) -> Result<Value, Vec<LoxError>> {
+        self.run_from(source, 0)
+    }
+
+    // Runs only `source[start..]`, where `start` is the beginning of a line,
+    // with spans pointing into the whole of `source`. A REPL passes
+    // everything entered so far, so an error in a function defined on an
+    // earlier line can still be shown against that line.
+    pub fn run_from(&mut self, source: &str, start: usize) -> Result<Value, Vec<LoxError>> {
+        let (statements, locals) = parse_program_from(source, start)?;
+        for (id, depth) in locals {
+            self.interpreter.resolve(id, depth);
         }
 
-        let mut interpreter: Interpreter = Interpreter::new();
+        self.interpreter
+            .interpret_stmts(&statements)
+            .map_err(|error| vec![error])
+    }
 
-        let _ = interpreter.interpret_stmts(statements).unwrap();
-        // Result is printed in interpreter
-        //println!("{:#?}", res);
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<Value, LoxError> {
+        let source: String = fs::read_to_string(&path).map_err(|e| {
+            LoxError::io(&format!(
+                "Could not read '{}': {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        self.eval(&source)
     }
 
-    pub fn parser_error(&mut self, token: Token, message: &str) {
-        self.had_error = true;
-        if token.token_type == TokenType::EOF {
-            self.report(token.line, "at end", message)
-        } else {
-            let loc_msg: String = format!("at '{}'", token.lexeme);
-            self.report(token.line, &loc_msg, message)
-        }
+    // Exposes a Rust closure to scripts, e.g.
+    // lox.define_native("double", 1, |args| Ok((args.get::<f64>(0)? * 2.0).into()))
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&NativeArgs) -> NativeResult + 'static,
+    {
+        self.interpreter.define_native(name, arity, body);
     }
 
-    pub fn runtime_error(&mut self, err: RuntimeError) {
-        println!("{} \n [line {} ]", err.reason, err.token.line);
-        self.had_runtime_error = true;
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        // A name never interned can't have been defined
+        let name: Symbol = Symbol::lookup(name)?;
+        self.interpreter.globals.borrow().values.get(&name).cloned()
     }
 
-    pub fn error(&mut self, line: u32, message: &str) {
-        self.report(line, "", message);
-        self.had_error = true;
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.interpreter
+            .globals
+            .borrow_mut()
+            .define(Symbol::intern(name), value);
     }
+}
+
+// Statements with the scope distance of each local, keyed by expression id
+pub(crate) type ResolvedProgram = (Vec<Stmt>, HashMap<usize, usize>);
+
+// Front end shared by both backends: scans, parses and resolves the source
+pub(crate) fn parse_program(source: &str) -> Result<ResolvedProgram, Vec<LoxError>> {
+    parse_program_from(source, 0)
+}
+
+// Same, for the part of the source from byte `start` on, see `Lox::run_from`
+pub(crate) fn parse_program_from(
+    source: &str,
+    start: usize,
+) -> Result<ResolvedProgram, Vec<LoxError>> {
+    let line: u32 = source[..start].matches('\n').count() as u32 + 1;
+    let mut scanner: Scanner = Scanner::new(source.to_string()).starting_at(start, line);
+    let tokens: Vec<Token> = scanner.scan_tokens()?;
+
+    let mut parser: Parser = Parser::new(tokens);
+    let statements: Vec<Stmt> = parser.parse_stmts()?;
+
+    let mut resolver: Resolver = Resolver::new();
+    resolver.resolve_stmts(&statements);
+    if !resolver.errors.is_empty() {
+        return Err(resolver.errors);
+    }
+
+    Ok((statements, resolver.locals))
+}
 
-    fn report(&self, line: u32, loc: &str, message: &str) {
-        println!("[line {}] Error {}: {}", line, loc, message);
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -1,28 +1,248 @@
-mod lox;
-
-use lox::Lox;
-use std::env;
-mod environment;
-mod expr;
-mod interpreter;
-mod interpreter_objects;
-mod parser;
-mod scanner;
-mod stmt;
-mod token;
-mod token_type;
+use std::{
+    env, fs,
+    io::{stderr, stdin, stdout, IsTerminal, Write},
+    path::Path,
+    process, thread,
+};
+
+use rlox::{compile, disassemble, disassemble_compiled, is_compiled, Lox, LoxError, Renderer, Vm};
+
+const USAGE: &str =
+    "Usage: rlox [--plain] [--vm] [--disassemble] [--trace] [--gc-stress] [--gc-growth=N] [script]
+       rlox compile <script.lox> [-o <script.loxc>]
+       rlox run <script.lox | script.loxc>";
+
+// Which implementation runs the code, the tree-walker unless `--vm` is given
+enum Backend {
+    TreeWalker(Lox),
+    Bytecode(Box<Vm>),
+}
+
+impl Backend {
+    fn run_from(&mut self, source: &str, start: usize) -> Result<(), Vec<LoxError>> {
+        match self {
+            Backend::TreeWalker(lox) => lox.run_from(source, start).map(|_| ()),
+            Backend::Bytecode(vm) => vm.run_from(source, start),
+        }
+    }
+
+    // Compiled scripts always run on the VM, whichever backend was picked
+    fn run_compiled(&mut self, bytes: &[u8]) -> Result<(), Vec<LoxError>> {
+        let result: Result<(), LoxError> = match self {
+            Backend::TreeWalker(_) => Vm::new().run_compiled(bytes),
+            Backend::Bytecode(vm) => vm.run_compiled(bytes),
+        };
+        result.map_err(|error| vec![error])
+    }
+}
+
+// Command line driver around the library: runs a script or a REPL and shows
+// errors as diagnostics.
+struct Cli {
+    backend: Backend,
+    // List the compiled bytecode instead of running it
+    disassemble: bool,
+    // Colour diagnostics, on by default when they go to a terminal
+    color: bool,
+}
+
+impl Cli {
+    fn execute(&mut self, source: &str) -> Result<(), Vec<LoxError>> {
+        if self.disassemble {
+            print!("{}", disassemble(source)?);
+            return Ok(());
+        }
+        self.backend.run_from(source, 0)
+    }
+
+    // Runs a script, or a .loxc file without going through the front end
+    fn run_file(&mut self, file_path: &str) {
+        let bytes: Vec<u8> = self.read_file(file_path);
+        if is_compiled(&bytes) {
+            let result: Result<(), Vec<LoxError>> = if self.disassemble {
+                disassemble_compiled(&bytes)
+                    .map(|listing| print!("{}", listing))
+                    .map_err(|error| vec![error])
+            } else {
+                self.backend.run_compiled(&bytes)
+            };
+            if let Err(errors) = result {
+                // There is no source to quote, errors only give the location
+                self.exit_with(&errors, "", file_path);
+            }
+            return;
+        }
+
+        let content: String = String::from_utf8_lossy(&bytes).into_owned();
+        if let Err(errors) = self.execute(&content) {
+            self.exit_with(&errors, &content, file_path);
+        }
+    }
+
+    fn compile_file(&self, file_path: &str, output_path: &str) {
+        let content: String = String::from_utf8_lossy(&self.read_file(file_path)).into_owned();
+        let bytes: Vec<u8> = match compile(&content) {
+            Ok(bytes) => bytes,
+            Err(errors) => self.exit_with(&errors, &content, file_path),
+        };
+        if let Err(e) = fs::write(output_path, bytes) {
+            let error: LoxError =
+                LoxError::io(&format!("Could not write '{}': {}", output_path, e));
+            self.report(&[error], "", output_path);
+            process::exit(74);
+        }
+    }
+
+    // Reported like `Lox::run_file` does, exiting with EX_IOERR
+    fn read_file(&self, file_path: &str) -> Vec<u8> {
+        match fs::read(file_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let error: LoxError =
+                    LoxError::io(&format!("Could not read '{}': {}", file_path, e));
+                self.report(&[error], "", file_path);
+                process::exit(74);
+            }
+        }
+    }
+
+    fn exit_with(&self, errors: &[LoxError], source: &str, file_name: &str) -> ! {
+        self.report(errors, source, file_name);
+        if errors.iter().any(|error| !error.is_runtime()) {
+            process::exit(65);
+        }
+        process::exit(70);
+    }
+
+    // A mistake on one line is reported and the session carries on, with
+    // the globals defined so far
+    fn repl(&mut self) {
+        // Every line entered, so errors can quote lines before the current
+        let mut session: String = String::new();
+        loop {
+            print!("> ");
+            let _ = stdout().flush();
+            let mut input: String = String::new();
+            let read: usize = stdin()
+                .read_line(&mut input)
+                .expect("Err while reading input line");
+            // End of input, e.g. Ctrl-D or a piped script running out
+            if read == 0 {
+                println!();
+                return;
+            }
+            let prettify_input: String = input.trim().to_string();
+            if prettify_input == ".exit" {
+                process::exit(1);
+            }
+            // Listings don't define anything, so only need the current line
+            if self.disassemble {
+                if let Err(errors) = self.execute(&prettify_input) {
+                    self.report(&errors, &prettify_input, "<repl>");
+                }
+                continue;
+            }
+
+            let start: usize = session.len();
+            session.push_str(&prettify_input);
+            session.push('\n');
+            if let Err(errors) = self.backend.run_from(&session, start) {
+                self.report(&errors, &session, "<repl>");
+            }
+        }
+    }
+
+    fn report(&self, errors: &[LoxError], source: &str, file_name: &str) {
+        let renderer: Renderer = Renderer::new(source, file_name, self.color);
+        for error in errors {
+            eprintln!("{}", renderer.render(error));
+        }
+    }
+}
+
+// Bad invocations exit with EX_USAGE so wrapping scripts can tell them apart
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    eprintln!("{}", USAGE);
+    process::exit(64);
+}
+
+// The tree-walker recurses on the Rust stack for every Lox call, this gives
+// it room for the full call depth even in debug builds
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let mut lox_instance = Lox::new();
-
-    if args.len() == 1 {
-        println!("Arg len 1 => Run prompt!");
-        lox_instance.repl();
-    } else if args.len() == 2 {
-        println!("Arg len 2 => Run using file input");
-        lox_instance.run_file(&args[1]);
+    let cli = thread::Builder::new()
+        .name("interpreter".to_string())
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run_cli)
+        .expect("Failed to start the interpreter thread");
+    // A panic has already been reported by the thread itself
+    if cli.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run_cli() {
+    // Flags may go anywhere, everything else is positional
+    let (flags, args): (Vec<String>, Vec<String>) =
+        env::args().partition(|arg| arg.starts_with("--"));
+    let mut color: bool = stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let mut use_vm: bool = false;
+    let mut disassemble: bool = false;
+    let mut trace: bool = false;
+    let mut gc_stress: bool = false;
+    let mut gc_growth: Option<f64> = None;
+
+    for flag in &flags {
+        match flag.as_str() {
+            // Diagnostics without colour, for CI logs
+            "--plain" => color = false,
+            "--vm" => use_vm = true,
+            "--disassemble" => disassemble = true,
+            // Only the VM can be traced, so this implies --vm
+            "--trace" => trace = true,
+            // Collect on every allocation, also implies --vm
+            "--gc-stress" => gc_stress = true,
+            _ if flag.starts_with("--gc-growth=") => {
+                match flag["--gc-growth=".len()..].parse::<f64>() {
+                    Ok(factor) if factor >= 1.0 => gc_growth = Some(factor),
+                    _ => usage_error("--gc-growth takes a number of at least 1."),
+                }
+            }
+            _ => usage_error(&format!("Unknown flag '{}'.", flag)),
+        }
+    }
+
+    let backend: Backend = if use_vm || trace || gc_stress || gc_growth.is_some() {
+        let mut vm: Vm = Vm::new();
+        if trace {
+            vm.set_trace(Some(Box::new(stderr())));
+        }
+        vm.set_gc_stress(gc_stress);
+        if let Some(factor) = gc_growth {
+            vm.set_gc_growth_factor(factor);
+        }
+        Backend::Bytecode(Box::new(vm))
     } else {
-        println!("Arg len invalid => exiting program..");
+        Backend::TreeWalker(Lox::new())
+    };
+    let mut cli: Cli = Cli {
+        backend,
+        disassemble,
+        color,
+    };
+
+    let args: Vec<&str> = args.iter().skip(1).map(String::as_str).collect();
+    match args.as_slice() {
+        [] => cli.repl(),
+        ["compile", input, "-o", output] => cli.compile_file(input, output),
+        ["compile", input] => {
+            let output = Path::new(input).with_extension("loxc");
+            cli.compile_file(input, &output.to_string_lossy());
+        }
+        ["compile"] | ["run"] => usage_error("Missing script."),
+        ["run", script] | [script] => cli.run_file(script),
+        _ => usage_error("Wrong number of arguments."),
     }
 }
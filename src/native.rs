@@ -0,0 +1,122 @@
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::value::Value;
+
+// What a native function body returns, an error message becomes a Lox
+// runtime error at the call site.
+pub type NativeResult = Result<Value, String>;
+
+type NativeBody = Box<dyn Fn(&NativeArgs) -> NativeResult>;
+
+// A function implemented in Rust and callable from Lox
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    body: NativeBody,
+}
+
+impl NativeFunction {
+    pub fn new<F>(name: &str, arity: usize, body: F) -> Self
+    where
+        F: Fn(&NativeArgs) -> NativeResult + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            arity,
+            body: Box::new(body),
+        }
+    }
+
+    pub fn call(&self, arguments: &[Value]) -> NativeResult {
+        (self.body)(&NativeArgs { values: arguments })
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+// Arguments of a native call, the count has already been checked
+pub struct NativeArgs<'a> {
+    values: &'a [Value],
+}
+
+impl NativeArgs<'_> {
+    // The argument at `index` as a Rust type, e.g. `args.get::<f64>(0)?`
+    pub fn get<T: FromValue>(&self, index: usize) -> Result<T, String> {
+        let value: &Value = self
+            .values
+            .get(index)
+            .ok_or_else(|| format!("Missing argument {}.", index + 1))?;
+
+        T::from_value(value)
+            .ok_or_else(|| format!("Argument {} must be a {}.", index + 1, T::TYPE_NAME))
+    }
+
+    pub fn values(&self) -> &[Value] {
+        self.values
+    }
+}
+
+// Rust types a Lox value can be extracted as
+pub trait FromValue: Sized {
+    // Used in the error when the value has another type
+    const TYPE_NAME: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for f64 {
+    const TYPE_NAME: &'static str = "number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(val) => Some(*val),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(val) => Some(*val),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const TYPE_NAME: &'static str = "string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Str(val) => Some(val.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Value {
+    const TYPE_NAME: &'static str = "value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+// Seconds since the Unix epoch, for timing scripts
+pub fn clock(_args: &NativeArgs) -> NativeResult {
+    let now: f64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs_f64();
+    Ok(Value::Number(now))
+}
@@ -0,0 +1,322 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{chunk::FunctionProto, native::NativeFunction, value::format_number};
+
+// Handle to an object living in the VM heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjRef(usize);
+
+// Runtime values of the bytecode VM. Anything bigger than a number lives in
+// the heap, strings being interned so equal strings share one ObjRef.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VmValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+impl VmValue {
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, VmValue::Nil | VmValue::Bool(false))
+    }
+}
+
+#[derive(Debug)]
+pub enum Obj {
+    Str(String),
+    Native(Rc<NativeFunction>),
+    Closure(ObjClosure),
+    Upvalue(ObjUpvalue),
+    Class(ObjClass),
+    Instance(ObjInstance),
+    BoundMethod(ObjBoundMethod),
+}
+
+impl Obj {
+    // Rough footprint for deciding when to collect. Instances growing new
+    // fields later are not accounted for.
+    fn size(&self) -> usize {
+        let contents: usize = match self {
+            Obj::Str(text) => text.len(),
+            Obj::Closure(closure) => closure.upvalues.len() * std::mem::size_of::<ObjRef>(),
+            Obj::Class(class) => class.name.len(),
+            Obj::Native(_) | Obj::Upvalue(_) | Obj::Instance(_) | Obj::BoundMethod(_) => 0,
+        };
+        std::mem::size_of::<Obj>() + contents
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjClosure {
+    pub function: Rc<FunctionProto>,
+    pub upvalues: Vec<ObjRef>,
+}
+
+// A captured variable, still on the stack while its scope is alive and
+// moved into the upvalue once the scope ends
+#[derive(Debug)]
+pub enum ObjUpvalue {
+    Open(usize),
+    Closed(VmValue),
+}
+
+#[derive(Debug)]
+pub struct ObjClass {
+    pub name: String,
+    // Interned method name to closure
+    pub methods: HashMap<ObjRef, ObjRef>,
+}
+
+#[derive(Debug)]
+pub struct ObjInstance {
+    pub class: ObjRef,
+    pub fields: HashMap<ObjRef, VmValue>,
+}
+
+#[derive(Debug)]
+pub struct ObjBoundMethod {
+    pub receiver: VmValue,
+    pub method: ObjRef,
+}
+
+// Bytes allocated before the first collection
+const INITIAL_GC_THRESHOLD: usize = 1024 * 1024;
+pub const DEFAULT_GROWTH_FACTOR: f64 = 2.0;
+
+// Counters for tuning the collector, see `Vm::gc_stats`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GcStats {
+    pub collections: usize,
+    // Objects alive after the last allocation or collection
+    pub objects: usize,
+    pub bytes_allocated: usize,
+}
+
+// Arena of every VM object, reclaimed by mark and sweep. The heap only
+// knows how to trace from roots it is given, the VM decides when to collect
+// and what the roots are.
+#[derive(Debug)]
+pub struct Heap {
+    // Freed slots are None and reused by later allocations
+    objects: Vec<Option<Obj>>,
+    free: Vec<usize>,
+    // Size each object was charged at when allocated
+    sizes: Vec<usize>,
+    marks: Vec<bool>,
+    // Marked objects whose children haven't been marked yet
+    gray: Vec<ObjRef>,
+    // Weak: an unreachable string is dropped from the table when swept
+    strings: HashMap<String, ObjRef>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    growth_factor: f64,
+    // Collect before every allocation, to flush out missing roots
+    stress: bool,
+    collections: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            free: Vec::new(),
+            sizes: Vec::new(),
+            marks: Vec::new(),
+            gray: Vec::new(),
+            strings: HashMap::new(),
+            bytes_allocated: 0,
+            next_gc: INITIAL_GC_THRESHOLD,
+            growth_factor: DEFAULT_GROWTH_FACTOR,
+            stress: false,
+            collections: 0,
+        }
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        self.growth_factor = growth_factor.max(1.0);
+    }
+
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.stress || self.bytes_allocated > self.next_gc
+    }
+
+    pub fn stats(&self) -> GcStats {
+        GcStats {
+            collections: self.collections,
+            objects: self.objects.len() - self.free.len(),
+            bytes_allocated: self.bytes_allocated,
+        }
+    }
+
+    pub fn alloc(&mut self, obj: Obj) -> ObjRef {
+        let size: usize = obj.size();
+        self.bytes_allocated += size;
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index] = Some(obj);
+                self.sizes[index] = size;
+                ObjRef(index)
+            }
+            None => {
+                self.objects.push(Some(obj));
+                self.sizes.push(size);
+                self.marks.push(false);
+                ObjRef(self.objects.len() - 1)
+            }
+        }
+    }
+
+    pub fn find_string(&self, text: &str) -> Option<ObjRef> {
+        self.strings.get(text).copied()
+    }
+
+    // The one string object holding `text`, created on first use
+    pub fn intern(&mut self, text: &str) -> ObjRef {
+        if let Some(existing) = self.find_string(text) {
+            return existing;
+        }
+        let reference: ObjRef = self.alloc(Obj::Str(text.to_string()));
+        self.strings.insert(text.to_string(), reference);
+        reference
+    }
+
+    pub fn get(&self, reference: ObjRef) -> &Obj {
+        match &self.objects[reference.0] {
+            Some(obj) => obj,
+            None => panic!("Use of freed object {:?}", reference),
+        }
+    }
+
+    pub fn get_mut(&mut self, reference: ObjRef) -> &mut Obj {
+        match &mut self.objects[reference.0] {
+            Some(obj) => obj,
+            None => panic!("Use of freed object {:?}", reference),
+        }
+    }
+
+    // The text of an object the compiler guarantees to be a string, such as
+    // a variable or property name
+    pub fn string(&self, reference: ObjRef) -> &str {
+        match self.get(reference) {
+            Obj::Str(text) => text,
+            other => panic!("Expected a string object, found {:?}", other),
+        }
+    }
+
+    pub fn closure(&self, reference: ObjRef) -> &ObjClosure {
+        match self.get(reference) {
+            Obj::Closure(closure) => closure,
+            other => panic!("Expected a closure object, found {:?}", other),
+        }
+    }
+
+    pub fn mark_value(&mut self, value: VmValue) {
+        if let VmValue::Obj(reference) = value {
+            self.mark_object(reference);
+        }
+    }
+
+    pub fn mark_object(&mut self, reference: ObjRef) {
+        if !self.marks[reference.0] {
+            self.marks[reference.0] = true;
+            self.gray.push(reference);
+        }
+    }
+
+    // Marks everything reachable from the roots marked so far
+    pub fn trace(&mut self) {
+        let mut children: Vec<VmValue> = Vec::new();
+        while let Some(reference) = self.gray.pop() {
+            match self.get(reference) {
+                Obj::Str(_) | Obj::Native(_) => (),
+                Obj::Closure(closure) => {
+                    children.extend(closure.upvalues.iter().map(|r| VmValue::Obj(*r)));
+                }
+                Obj::Upvalue(ObjUpvalue::Closed(value)) => children.push(*value),
+                Obj::Upvalue(ObjUpvalue::Open(_)) => (),
+                Obj::Class(class) => {
+                    for (name, method) in &class.methods {
+                        children.push(VmValue::Obj(*name));
+                        children.push(VmValue::Obj(*method));
+                    }
+                }
+                Obj::Instance(instance) => {
+                    children.push(VmValue::Obj(instance.class));
+                    for (name, value) in &instance.fields {
+                        children.push(VmValue::Obj(*name));
+                        children.push(*value);
+                    }
+                }
+                Obj::BoundMethod(bound) => {
+                    children.push(bound.receiver);
+                    children.push(VmValue::Obj(bound.method));
+                }
+            }
+            for child in children.drain(..) {
+                self.mark_value(child);
+            }
+        }
+    }
+
+    // Frees every unmarked object and clears the marks for next time
+    pub fn sweep(&mut self) {
+        let marks: &Vec<bool> = &self.marks;
+        self.strings.retain(|_, reference| marks[reference.0]);
+
+        for index in 0..self.objects.len() {
+            if self.marks[index] {
+                self.marks[index] = false;
+            } else if self.objects[index].is_some() {
+                self.objects[index] = None;
+                self.bytes_allocated -= self.sizes[index];
+                self.free.push(index);
+            }
+        }
+
+        self.collections += 1;
+        let grown: f64 = self.bytes_allocated as f64 * self.growth_factor;
+        self.next_gc = (grown as usize).max(INITIAL_GC_THRESHOLD);
+    }
+
+    // How `print` shows a value, matching the tree-walker
+    pub fn format(&self, value: VmValue) -> String {
+        match value {
+            VmValue::Nil => "nil".to_string(),
+            VmValue::Bool(val) => val.to_string(),
+            VmValue::Number(val) => format_number(val),
+            VmValue::Obj(reference) => self.format_obj(reference),
+        }
+    }
+
+    fn format_obj(&self, reference: ObjRef) -> String {
+        match self.get(reference) {
+            Obj::Str(text) => text.clone(),
+            Obj::Native(_) => "<native fn>".to_string(),
+            Obj::Closure(closure) => {
+                if closure.function.name.is_empty() {
+                    "<script>".to_string()
+                } else {
+                    format!("<fn {}>", closure.function.name)
+                }
+            }
+            Obj::Upvalue(_) => "upvalue".to_string(),
+            Obj::Class(class) => class.name.clone(),
+            Obj::Instance(instance) => match self.get(instance.class) {
+                Obj::Class(class) => format!("{} instance", class.name),
+                _ => "instance".to_string(),
+            },
+            Obj::BoundMethod(bound) => self.format_obj(bound.method),
+        }
+    }
+}
@@ -1,17 +1,23 @@
-use core::panic;
-use std::num::ParseFloatError;
+use std::{num::ParseFloatError, rc::Rc};
 
 use crate::{
-    expr::{Expr, LiteralRepresentations},
-    lox::{self, Lox},
-    stmt::{Expression, Print, Stmt, Var},
-    token::{self, Token},
+    error::LoxError,
+    expr::{next_expr_id, Expr, LiteralRepresentations},
+    stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While},
+    token::{Span, Token},
     token_type::TokenType,
 };
 
+// Upper bound on parameters of a declaration and arguments of a call
+const MAX_ARGUMENTS: usize = 255;
+
+type ParseResult<T> = Result<T, LoxError>;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: u32,
+    // Errors that don't leave the parser confused, so parsing carries on
+    errors: Vec<LoxError>,
 }
 
 impl Parser {
@@ -19,99 +25,416 @@ impl Parser {
         Self {
             tokens: tokens,
             current: 0,
+            errors: Vec::new(),
         }
     }
 
-    pub fn parse_stmts(&mut self) -> Vec<Stmt> {
+    // Parses the whole program, carrying on past syntax errors so all of them
+    // are reported together.
+    pub fn parse_stmts(&mut self) -> Result<Vec<Stmt>, Vec<LoxError>> {
         let mut statements: Vec<Stmt> = Vec::new();
 
         while !self.is_at_end() {
             if let Some(s) = self.declaration() {
                 statements.push(s);
             }
-
-            //statements.push(self.statement());
         }
 
-        statements
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(statements)
     }
 
+    // Statement boundary where the parser recovers from a syntax error. The
+    // error is recorded and the rest of the broken statement skipped, giving
+    // None in place of the statement.
     fn declaration(&mut self) -> Option<Stmt> {
-        if self.matches(vec![TokenType::VAR]) {
-            return Some(self.var_declaration());
-        } else if !self.matches(vec![TokenType::VAR]) {
-            return Some(self.statement());
+        let declaration: ParseResult<Stmt> = if self.matches(vec![TokenType::CLASS]) {
+            self.class_declaration()
+        } else if self.matches(vec![TokenType::FUN]) {
+            self.function("function")
+                .map(|function| Stmt::Function(Rc::new(function)))
+        } else if self.matches(vec![TokenType::VAR]) {
+            self.var_declaration()
         } else {
-            self.synchronize();
-            return None;
+            self.statement()
+        };
+
+        match declaration {
+            Ok(s) => return Some(s),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                return None;
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> ParseResult<Stmt> {
+        let keyword: Token = self.previous().clone();
+        let name: Token = self.consume(TokenType::IDENTIFIER, "Expect class name.")?;
+
+        let mut superclass: Option<Expr> = None;
+        if self.matches(vec![TokenType::LESS]) {
+            let superclass_name: Token =
+                self.consume(TokenType::IDENTIFIER, "Expect superclass name.")?;
+            superclass = Some(Expr::Variable {
+                id: next_expr_id(),
+                span: superclass_name.span,
+                name: superclass_name,
+            });
+        }
+
+        self.consume(TokenType::LEFTBRACE, "Expect '{' before class body.")?;
+
+        let mut methods: Vec<Rc<Function>> = Vec::new();
+        while !self.check(TokenType::RIGHTBRACE) && !self.is_at_end() {
+            methods.push(Rc::new(self.function("method")?));
         }
+
+        let right_brace: Token =
+            self.consume(TokenType::RIGHTBRACE, "Expect '}' after class body.")?;
+        return Ok(Stmt::Class(Class {
+            name,
+            superclass,
+            methods,
+            span: keyword.span.to(&right_brace.span),
+        }));
     }
 
-    fn var_declaration(&mut self) -> Stmt {
-        let name: Token = self
-            .consume(TokenType::IDENTIFIER, "Expect variable name")
-            .unwrap();
+    fn function(&mut self, kind: &str) -> ParseResult<Function> {
+        let name: Token = self.consume(TokenType::IDENTIFIER, "Expect function name.")?;
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after function name.")?;
+
+        let mut params: Vec<Token> = Vec::new();
+        if !self.check(TokenType::RIGHTPAREN) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let token: Token = self.peek().clone();
+                    self.error(token, "Can't have more than 255 parameters.");
+                }
+                params.push(self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?);
+                if !self.matches(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LEFTBRACE,
+            if kind == "method" {
+                "Expect '{' before method body."
+            } else {
+                "Expect '{' before function body."
+            },
+        )?;
+        let body: Vec<Stmt> = self.block()?;
+
+        // Runs from the name, `fun` isn't written for methods
+        let span: Span = name.span.to(&self.previous().span);
+        return Ok(Function {
+            name,
+            params,
+            body,
+            span,
+        });
+    }
+
+    fn var_declaration(&mut self) -> ParseResult<Stmt> {
+        let keyword: Token = self.previous().clone();
+        let name: Token = self.consume(TokenType::IDENTIFIER, "Expect variable name.")?;
 
         let mut initializer = None;
         if self.matches(vec![TokenType::EQUAL]) {
-            initializer = Some(self.expression());
+            initializer = Some(self.expression()?);
         }
 
-        self.consume(
+        let semicolon: Token = self.consume(
             TokenType::SEMICOLON,
             "Expect ';' after variable declaration.",
-        );
-        return Stmt::Var(Var { name, initializer });
+        )?;
+        return Ok(Stmt::Var(Var {
+            name,
+            initializer,
+            span: keyword.span.to(&semicolon.span),
+        }));
     }
 
-    fn statement(&mut self) -> Stmt {
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        if self.matches(vec![TokenType::FOR]) {
+            return self.for_statement();
+        }
+        if self.matches(vec![TokenType::IF]) {
+            return self.if_statement();
+        }
         if self.matches(vec![TokenType::PRINT]) {
             return self.print_statement();
         }
+        if self.matches(vec![TokenType::RETURN]) {
+            return self.return_statement();
+        }
+        if self.matches(vec![TokenType::WHILE]) {
+            return self.while_statement();
+        }
+        if self.matches(vec![TokenType::LEFTBRACE]) {
+            let left_brace: Token = self.previous().clone();
+            let statements: Vec<Stmt> = self.block()?;
+            return Ok(Stmt::Block(Block {
+                statements,
+                span: left_brace.span.to(&self.previous().span),
+            }));
+        }
 
         self.expression_statement()
     }
 
-    fn print_statement(&mut self) -> Stmt {
-        let print_expr: Expr = self.expression();
-        self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
-        return Stmt::Print(Print {
-            print_expression: print_expr,
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut statements: Vec<Stmt> = Vec::new();
+
+        while !self.check(TokenType::RIGHTBRACE) && !self.is_at_end() {
+            if let Some(s) = self.declaration() {
+                statements.push(s);
+            }
+        }
+
+        self.consume(TokenType::RIGHTBRACE, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    // There is no for node in the syntax tree, the loop is desugared into
+    // { initializer; while (condition) { body; increment; } }
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        // The generated nodes all point back at the whole loop
+        let keyword: Token = self.previous().clone();
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after 'for'.")?;
+
+        let initializer: Option<Stmt> = if self.matches(vec![TokenType::SEMICOLON]) {
+            None
+        } else if self.matches(vec![TokenType::VAR]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let mut condition: Option<Expr> = None;
+        if !self.check(TokenType::SEMICOLON) {
+            condition = Some(self.expression()?);
+        }
+        self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.")?;
+
+        let mut increment: Option<Expr> = None;
+        if !self.check(TokenType::RIGHTPAREN) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after for clauses.")?;
+
+        let mut body: Stmt = self.statement()?;
+        let span: Span = keyword.span.to(&body.span());
+
+        if let Some(increment) = increment {
+            let increment_span: Span = increment.span();
+            body = Stmt::Block(Block {
+                statements: vec![
+                    body,
+                    Stmt::Expression(Expression {
+                        expression: increment,
+                        span: increment_span,
+                    }),
+                ],
+                span,
+            });
+        }
+
+        body = Stmt::While(While {
+            condition: condition.unwrap_or(Expr::Literal {
+                literal: LiteralRepresentations::CustomBoolean { val: true },
+                span: keyword.span,
+            }),
+            body: Box::new(body),
+            span,
         });
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(Block {
+                statements: vec![initializer, body],
+                span,
+            });
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword: Token = self.previous().clone();
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after 'if'.")?;
+        let condition: Expr = self.expression()?;
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after if condition.")?;
+
+        let then_branch: Box<Stmt> = Box::new(self.statement()?);
+        let mut else_branch: Option<Box<Stmt>> = None;
+        if self.matches(vec![TokenType::ELSE]) {
+            else_branch = Some(Box::new(self.statement()?));
+        }
+
+        let span: Span = match &else_branch {
+            Some(else_branch) => keyword.span.to(&else_branch.span()),
+            None => keyword.span.to(&then_branch.span()),
+        };
+        return Ok(Stmt::If(If {
+            condition,
+            then_branch,
+            else_branch,
+            span,
+        }));
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword: Token = self.previous().clone();
+        let mut value: Option<Expr> = None;
+        if !self.check(TokenType::SEMICOLON) {
+            value = Some(self.expression()?);
+        }
+
+        let semicolon: Token =
+            self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
+        return Ok(Stmt::Return(Return {
+            span: keyword.span.to(&semicolon.span),
+            keyword,
+            value,
+        }));
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword: Token = self.previous().clone();
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after 'while'.")?;
+        let condition: Expr = self.expression()?;
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after condition.")?;
+        let body: Box<Stmt> = Box::new(self.statement()?);
+
+        return Ok(Stmt::While(While {
+            span: keyword.span.to(&body.span()),
+            condition,
+            body,
+        }));
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword: Token = self.previous().clone();
+        let print_expr: Expr = self.expression()?;
+        let semicolon: Token = self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
+        return Ok(Stmt::Print(Print {
+            print_expression: print_expr,
+            span: keyword.span.to(&semicolon.span),
+        }));
     }
 
-    fn expression_statement(&mut self) -> Stmt {
-        let expr: Expr = self.expression();
-        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.");
-        return Stmt::Expression(Expression { expression: expr });
+    fn expression_statement(&mut self) -> ParseResult<Stmt> {
+        let expr: Expr = self.expression()?;
+        let semicolon: Token =
+            self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
+        return Ok(Stmt::Expression(Expression {
+            span: expr.span().to(&semicolon.span),
+            expression: expr,
+        }));
     }
 
-    pub fn parse(&mut self) -> Expr {
-        self.expression()
+    fn expression(&mut self) -> ParseResult<Expr> {
+        self.assignment()
     }
 
-    fn expression(&mut self) -> Expr {
-        self.equality()
+    fn assignment(&mut self) -> ParseResult<Expr> {
+        let expr: Expr = self.logic_or()?;
+
+        if self.matches(vec![TokenType::EQUAL]) {
+            let equals: Token = self.previous().clone();
+            // Assignment is right-associative, so parse the value recursively
+            let value: Expr = self.assignment()?;
+
+            let target: Span = expr.span();
+            let span: Span = target.to(&value.span());
+            match expr {
+                Expr::Variable { name, .. } => {
+                    return Ok(Expr::Assign {
+                        id: next_expr_id(),
+                        name,
+                        value: Box::new(value),
+                        span,
+                    });
+                }
+                Expr::Get { object, name, .. } => {
+                    return Ok(Expr::Set {
+                        object,
+                        name,
+                        value: Box::new(value),
+                        span,
+                    });
+                }
+                _ => (),
+            }
+
+            let error: LoxError =
+                LoxError::parse(equals, "Invalid assignment target.").with_span(target);
+            self.errors.push(error);
+        }
+
+        Ok(expr)
+    }
+
+    fn logic_or(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.logic_and()?;
+
+        while self.matches(vec![TokenType::OR]) {
+            let operator: Token = self.previous().clone();
+            let right: Expr = self.logic_and()?;
+            expr = Expr::Logical {
+                span: expr.span().to(&right.span()),
+                left: Box::new(expr),
+                operator: operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn logic_and(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.equality()?;
+
+        while self.matches(vec![TokenType::AND]) {
+            let operator: Token = self.previous().clone();
+            let right: Expr = self.equality()?;
+            expr = Expr::Logical {
+                span: expr.span().to(&right.span()),
+                left: Box::new(expr),
+                operator: operator,
+                right: Box::new(right),
+            }
+        }
+
+        Ok(expr)
     }
 
-    fn equality(&mut self) -> Expr {
-        let mut expr: Expr = self.comparison();
+    fn equality(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.comparison()?;
 
         while self.matches(vec![TokenType::BANGEQUAL, TokenType::EQUALEQUAL]) {
             let operator: Token = self.previous().clone();
-            let right: Expr = self.comparison();
+            let right: Expr = self.comparison()?;
             expr = Expr::Binary {
+                span: expr.span().to(&right.span()),
                 left: Box::new(expr),
                 operator: operator,
                 right: Box::new(right),
             }
         }
 
-        expr
+        Ok(expr)
     }
 
-    fn comparison(&mut self) -> Expr {
-        let mut expr: Expr = self.term();
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.term()?;
 
         while self.matches(vec![
             TokenType::GREATER,
@@ -120,124 +443,202 @@ impl Parser {
             TokenType::LESSEQUAL,
         ]) {
             let operator: Token = self.previous().clone();
-            let right: Expr = self.term();
+            let right: Expr = self.term()?;
             expr = Expr::Binary {
+                span: expr.span().to(&right.span()),
                 left: Box::new(expr),
                 operator: operator,
                 right: Box::new(right),
             }
         }
-        expr
+        Ok(expr)
     }
 
-    fn term(&mut self) -> Expr {
-        let mut expr: Expr = self.factor();
+    fn term(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.factor()?;
         while self.matches(vec![TokenType::MINUS, TokenType::PLUS]) {
             let operator: Token = self.previous().clone();
-            print!("{:#?}", operator);
-            let right: Expr = self.factor();
+            let right: Expr = self.factor()?;
             expr = Expr::Binary {
+                span: expr.span().to(&right.span()),
                 left: Box::new(expr),
                 operator: operator,
                 right: Box::new(right),
             }
         }
-        expr
+        Ok(expr)
     }
 
-    fn factor(&mut self) -> Expr {
-        let mut expr: Expr = self.unary();
+    fn factor(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.unary()?;
 
         while self.matches(vec![TokenType::SLASH, TokenType::STAR]) {
             let operator: Token = self.previous().clone();
-            let right: Expr = self.unary();
+            let right: Expr = self.unary()?;
             expr = Expr::Binary {
+                span: expr.span().to(&right.span()),
                 left: Box::new(expr),
                 operator: operator,
                 right: Box::new(right),
             }
         }
-        expr
+        Ok(expr)
     }
 
-    fn unary(&mut self) -> Expr {
+    fn unary(&mut self) -> ParseResult<Expr> {
         if self.matches(vec![TokenType::BANG, TokenType::MINUS]) {
             let operator: Token = self.previous().clone();
-            let right: Expr = self.unary();
-            return Expr::Unary {
+            let right: Expr = self.unary()?;
+            return Ok(Expr::Unary {
+                span: operator.span.to(&right.span()),
                 operator: operator,
                 right: Box::new(right),
-            };
+            });
         }
-        self.primary()
+        self.call()
+    }
+
+    fn call(&mut self) -> ParseResult<Expr> {
+        let mut expr: Expr = self.primary()?;
+
+        loop {
+            if self.matches(vec![TokenType::LEFTPAREN]) {
+                expr = self.finish_call(expr)?;
+            } else if self.matches(vec![TokenType::DOT]) {
+                let name: Token =
+                    self.consume(TokenType::IDENTIFIER, "Expect property name after '.'.")?;
+                expr = Expr::Get {
+                    span: expr.span().to(&name.span),
+                    object: Box::new(expr),
+                    name,
+                };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
     }
 
-    fn primary(&mut self) -> Expr {
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr> {
+        let mut arguments: Vec<Expr> = Vec::new();
+        if !self.check(TokenType::RIGHTPAREN) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let token: Token = self.peek().clone();
+                    self.error(token, "Can't have more than 255 arguments.");
+                }
+                arguments.push(self.expression()?);
+                if !self.matches(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
+        let paren: Token = self.consume(TokenType::RIGHTPAREN, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call {
+            span: callee.span().to(&paren.span),
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        })
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr> {
         if self.matches(vec![TokenType::FALSE]) {
-            return Expr::Literal {
+            return Ok(Expr::Literal {
                 literal: LiteralRepresentations::CustomBoolean { val: false },
-            };
+                span: self.previous().span,
+            });
         }
         if self.matches(vec![TokenType::TRUE]) {
-            return Expr::Literal {
+            return Ok(Expr::Literal {
                 literal: LiteralRepresentations::CustomBoolean { val: true },
-            };
+                span: self.previous().span,
+            });
         }
         if self.matches(vec![TokenType::NIL]) {
-            return Expr::Literal {
-                literal: LiteralRepresentations::CustomNil {
-                    val: "Null".to_string(),
-                },
-            };
+            return Ok(Expr::Literal {
+                literal: LiteralRepresentations::CustomNil,
+                span: self.previous().span,
+            });
         }
         if self.matches(vec![TokenType::NUMBER, TokenType::STRING]) {
             let tt: Token = self.previous().clone();
+            // The scanner gives every literal token its text
+            let literal: Rc<str> = tt.literal.clone().unwrap_or_default();
             if tt.token_type == TokenType::NUMBER {
-                let tt_val: Result<f64, ParseFloatError> = tt.literal.parse::<f64>();
+                let tt_val: Result<f64, ParseFloatError> = literal.parse::<f64>();
                 match tt_val {
                     Ok(val) => {
-                        return Expr::Literal {
+                        return Ok(Expr::Literal {
                             literal: LiteralRepresentations::CustomNumber { val },
-                        }
+                            span: tt.span,
+                        })
+                    }
+                    Err(e) => {
+                        let message: String = format!("Failed to parse number: {}.", e);
+                        return Err(LoxError::parse(tt, &message));
                     }
-                    Err(e) => panic!("Failed to parse float {}", e),
                 };
             } else {
-                return Expr::Literal {
-                    literal: LiteralRepresentations::CustomString { val: tt.literal },
-                };
+                return Ok(Expr::Literal {
+                    literal: LiteralRepresentations::CustomString { val: literal },
+                    span: tt.span,
+                });
             }
         }
 
+        if self.matches(vec![TokenType::SUPER]) {
+            let keyword: Token = self.previous().clone();
+            self.consume(TokenType::DOT, "Expect '.' after 'super'.")?;
+            let method: Token =
+                self.consume(TokenType::IDENTIFIER, "Expect superclass method name.")?;
+            return Ok(Expr::Super {
+                id: next_expr_id(),
+                span: keyword.span.to(&method.span),
+                keyword,
+                method,
+            });
+        }
+
+        if self.matches(vec![TokenType::THIS]) {
+            return Ok(Expr::This {
+                id: next_expr_id(),
+                span: self.previous().span,
+                keyword: self.previous().clone(),
+            });
+        }
+
         if self.matches(vec![TokenType::IDENTIFIER]) {
-            return Expr::Variable {
+            return Ok(Expr::Variable {
+                id: next_expr_id(),
+                span: self.previous().span,
                 name: self.previous().clone(),
-            };
+            });
         }
 
         if self.matches(vec![TokenType::LEFTPAREN]) {
-            let expr: Expr = self.expression();
-            self.consume(TokenType::RIGHTPAREN, "Expect ')' after expression.");
-            return Expr::Grouping {
+            let left_paren: Token = self.previous().clone();
+            let expr: Expr = self.expression()?;
+            let right_paren: Token =
+                self.consume(TokenType::RIGHTPAREN, "Expect ')' after expression.")?;
+            return Ok(Expr::Grouping {
                 expr: Box::new(expr),
-            };
+                span: left_paren.span.to(&right_paren.span),
+            });
         }
 
-        Expr::FailScenario {
-            reason: "Reached end, expecting an expression".to_string(),
-        }
+        Err(LoxError::parse(self.peek().clone(), "Expect expression."))
     }
 
-    fn consume(
-        &mut self,
-        token_type: TokenType,
-        msg: &'static str,
-    ) -> Result<Token, (&Token, &'static str)> {
+    fn consume(&mut self, token_type: TokenType, msg: &str) -> ParseResult<Token> {
         if self.check(token_type) {
             return Ok(self.advance());
         }
 
-        return Err((self.peek(), msg));
+        return Err(LoxError::parse(self.peek().clone(), msg));
     }
 
     fn matches(&mut self, token_type_vec: Vec<TokenType>) -> bool {
@@ -254,8 +655,7 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        let tt: Token = self.peek().clone();
-        tt.token_type == token_type
+        self.peek().token_type == token_type
     }
 
     fn advance(&mut self) -> Token {
@@ -266,8 +666,7 @@ impl Parser {
     }
 
     fn is_at_end(&self) -> bool {
-        let tt: Token = self.peek().clone();
-        tt.token_type == TokenType::EOF
+        self.peek().token_type == TokenType::EOF
     }
 
     fn peek(&self) -> &Token {
@@ -281,8 +680,9 @@ impl Parser {
         &self.tokens[self.current as usize - 1]
     }
 
-    fn error(&self, token: Token, message: &str, mut lox: Lox) {
-        lox.parser_error(token, message);
+    // Records an error without unwinding, for mistakes the parser can read past
+    fn error(&mut self, token: Token, message: &str) {
+        self.errors.push(LoxError::parse(token, message));
     }
 
     fn synchronize(&mut self) {
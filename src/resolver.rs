@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::LoxError,
+    expr::Expr,
+    stmt::{Block, Class, Expression, Function, If, Print, Return, Stmt, Var, While},
+    symbol::Symbol,
+    token::{Span, Token},
+};
+
+// A name declared in a local scope
+#[derive(Clone, Copy)]
+struct Local {
+    // False until the initializer has been resolved
+    defined: bool,
+    // Where it was declared, for pointing back at it in errors
+    span: Span,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// Static pass run between parsing and running. It works out how many scopes
+// out each local variable lives, for the tree-walker, and collects the errors
+// that can be found without running the code, for both backends.
+pub struct Resolver {
+    // Scope distance of each resolved local, keyed by expression id
+    pub locals: HashMap<usize, usize>,
+    // Innermost scope last
+    scopes: Vec<HashMap<Symbol, Local>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    pub errors: Vec<LoxError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            locals: HashMap::new(),
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn resolve_stmts(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(Block { statements, .. }) => {
+                self.begin_scope();
+                self.resolve_stmts(statements);
+                self.end_scope();
+            }
+            Stmt::Var(Var {
+                name, initializer, ..
+            }) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Function(function) => {
+                // Defined before the body is resolved so it can recurse
+                self.declare(&function.name);
+                self.define(&function.name);
+                self.resolve_function(function, FunctionType::Function);
+            }
+            Stmt::Class(Class {
+                name,
+                superclass,
+                methods,
+                ..
+            }) => {
+                let enclosing_class: ClassType = self.current_class;
+                self.current_class = ClassType::Class;
+
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable {
+                        name: superclass_name,
+                        ..
+                    } = superclass
+                    {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(superclass_name, "A class can't inherit from itself.");
+                        }
+                    }
+
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expr(superclass);
+
+                    // Scope between the class' and the methods' holding `super`
+                    self.begin_scope();
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(
+                            Symbol::super_(),
+                            Local {
+                                defined: true,
+                                span: name.span,
+                            },
+                        );
+                    }
+                }
+
+                // Methods are bound in a scope that only holds `this`
+                self.begin_scope();
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(
+                        Symbol::this(),
+                        Local {
+                            defined: true,
+                            span: name.span,
+                        },
+                    );
+                }
+                for method in methods {
+                    let function_type: FunctionType = if method.name.lexeme == Symbol::init() {
+                        FunctionType::Initializer
+                    } else {
+                        FunctionType::Method
+                    };
+                    self.resolve_function(method, function_type);
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Expression(Expression { expression, .. }) => self.resolve_expr(expression),
+            Stmt::Print(Print {
+                print_expression, ..
+            }) => self.resolve_expr(print_expression),
+            Stmt::If(If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            }) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While(While {
+                condition, body, ..
+            }) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+            Stmt::Return(Return {
+                keyword,
+                value,
+                span,
+            }) => {
+                if self.current_function == FunctionType::None {
+                    let error: LoxError =
+                        LoxError::resolve(keyword.clone(), "Can't return from top-level code.");
+                    self.errors.push(error.with_span(*span));
+                }
+                if let Some(value) = value {
+                    if self.current_function == FunctionType::Initializer {
+                        let error: LoxError = LoxError::resolve(
+                            keyword.clone(),
+                            "Can't return a value from an initializer.",
+                        );
+                        self.errors.push(error.with_span(value.span()));
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { id, name, .. } => {
+                let declared: Option<Local> = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&name.lexeme).copied());
+                if let Some(local) = declared {
+                    if !local.defined {
+                        let error: LoxError = LoxError::resolve(
+                            name.clone(),
+                            "Can't read local variable in its own initializer.",
+                        );
+                        self.errors
+                            .push(error.with_note(local.span, "variable declared here"));
+                    }
+                }
+                self.resolve_local(*id, name);
+            }
+            Expr::Assign {
+                id, name, value, ..
+            } => {
+                self.resolve_expr(value);
+                self.resolve_local(*id, name);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.resolve_expr(callee);
+                for argument in arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Get { object, .. } => self.resolve_expr(object),
+            Expr::Set { object, value, .. } => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::This { id, keyword, .. } => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::Super { id, keyword, .. } => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.error(keyword, "Can't use 'super' outside of a class.");
+                        return;
+                    }
+                    ClassType::Class => {
+                        self.error(keyword, "Can't use 'super' in a class with no superclass.");
+                        return;
+                    }
+                    ClassType::Subclass => (),
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::Grouping { expr, .. } => self.resolve_expr(expr),
+            Expr::Unary { right, .. } => self.resolve_expr(right),
+            Expr::Literal { .. } => (),
+        }
+    }
+
+    fn resolve_function(&mut self, function: &Function, function_type: FunctionType) {
+        let enclosing_function: FunctionType = self.current_function;
+        self.current_function = function_type;
+
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    // Not finding the name in any scope means it is a global
+    fn resolve_local(&mut self, id: usize, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.locals.insert(id, depth);
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if let Some(previous) = scope.get(&name.lexeme) {
+            let error: LoxError = LoxError::resolve(
+                name.clone(),
+                "Already a variable with this name in this scope.",
+            )
+            .with_note(previous.span, "first declared here");
+            self.errors.push(error);
+            return;
+        }
+        scope.insert(
+            name.lexeme,
+            Local {
+                defined: false,
+                span: name.span,
+            },
+        );
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(
+                name.lexeme,
+                Local {
+                    defined: true,
+                    span: name.span,
+                },
+            );
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors.push(LoxError::resolve(token.clone(), message));
+    }
+}
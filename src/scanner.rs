@@ -1,17 +1,33 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
-use crate::lox::Lox;
-use crate::token::{self, Token};
+use crate::error::LoxError;
+use crate::symbol::Symbol;
+use crate::token::{Span, Token};
 use crate::token_type::TokenType;
 
+// Walks the source once with a byte cursor that always sits on a char
+// boundary, so slicing out lexemes is cheap and safe for any UTF-8 text.
 pub struct Scanner {
     source: String,
     tokens: Vec<Token>,
-    start: u32,
-    current: u32,
+    // Byte offsets of the token start and the cursor
+    start: usize,
+    current: usize,
     line: u32,
+    // Column of the cursor, counted in chars
+    column: u32,
+    // Line and column of the token being scanned, `line` may have moved on
+    // by the time a multi-line string is added
+    start_line: u32,
+    start_column: u32,
     keywords: HashMap<String, TokenType>,
-    lox: Lox,
+    errors: Vec<LoxError>,
+}
+
+// Only the scanner, for benchmarking it without the rest of the front end
+#[doc(hidden)]
+pub fn scan(source: &str) -> Result<Vec<Token>, Vec<LoxError>> {
+    Scanner::new(source.to_string()).scan_tokens()
 }
 
 impl Scanner {
@@ -22,6 +38,9 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
             keywords: HashMap::from([
                 (String::from("and"), TokenType::AND),
                 (String::from("class"), TokenType::CLASS),
@@ -40,70 +59,83 @@ impl Scanner {
                 (String::from("var"), TokenType::VAR),
                 (String::from("while"), TokenType::WHILE),
             ]),
-            lox: Lox::new(),
+            errors: Vec::new(),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> &Vec<Token> {
+    // Skips to byte `offset`, which starts line `line`. Spans still count
+    // from the start of the whole source.
+    pub fn starting_at(mut self, offset: usize, line: u32) -> Self {
+        self.start = offset;
+        self.current = offset;
+        self.line = line;
+        self.start_line = line;
+        self
+    }
+
+    // Scans the whole source even after an error, so every bad character is
+    // reported in one go.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<LoxError>> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token();
         }
 
-        self.tokens.push(Token::new(
-            TokenType::EOF,
-            "".to_string(),
-            "Null".to_string(),
-            self.line,
-        ));
+        let eof: Span = Span::new(self.current, 0, self.line, self.column);
+        self.tokens
+            .push(Token::new(TokenType::EOF, Symbol::intern(""), None, eof));
 
-        // Return reference or add clone to Token type struct.
-        &self.tokens
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        Ok(std::mem::take(&mut self.tokens))
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len() as u32
+        self.current >= self.source.len()
     }
 
     fn scan_token(&mut self) {
         let c: char = self.advance();
         match c {
-            '(' => self.add_token(TokenType::LEFTPAREN, "LEFT PAREN".to_string()),
-            ')' => self.add_token(TokenType::RIGHTPAREN, "RIGHT PAREN".to_string()),
-            '{' => self.add_token(TokenType::LEFTBRACE, "LEFT BRACE".to_string()),
-            '}' => self.add_token(TokenType::RIGHTBRACE, "RIGHT BRACE".to_string()),
-            ',' => self.add_token(TokenType::COMMA, "COMMA".to_string()),
-            '.' => self.add_token(TokenType::DOT, "DOT".to_string()),
-            '-' => self.add_token(TokenType::MINUS, "MINUS".to_string()),
-            '+' => self.add_token(TokenType::PLUS, "PLUS".to_string()),
-            ';' => self.add_token(TokenType::SEMICOLON, "SEMICOLON".to_string()),
-            '*' => self.add_token(TokenType::STAR, "STAR".to_string()),
+            '(' => self.add_token(TokenType::LEFTPAREN),
+            ')' => self.add_token(TokenType::RIGHTPAREN),
+            '{' => self.add_token(TokenType::LEFTBRACE),
+            '}' => self.add_token(TokenType::RIGHTBRACE),
+            ',' => self.add_token(TokenType::COMMA),
+            '.' => self.add_token(TokenType::DOT),
+            '-' => self.add_token(TokenType::MINUS),
+            '+' => self.add_token(TokenType::PLUS),
+            ';' => self.add_token(TokenType::SEMICOLON),
+            '*' => self.add_token(TokenType::STAR),
             '!' => {
                 if self.matches('=') {
-                    self.add_token(TokenType::BANGEQUAL, "BANG EQUAL".to_string())
+                    self.add_token(TokenType::BANGEQUAL)
                 } else {
-                    self.add_token(TokenType::BANG, "BANG".to_string())
+                    self.add_token(TokenType::BANG)
                 }
             }
             '=' => {
                 if self.matches('=') {
-                    self.add_token(TokenType::EQUALEQUAL, "EQUAL EQUAL".to_string())
+                    self.add_token(TokenType::EQUALEQUAL)
                 } else {
-                    self.add_token(TokenType::EQUAL, "EQUAL".to_string())
+                    self.add_token(TokenType::EQUAL)
                 }
             }
             '<' => {
                 if self.matches('=') {
-                    self.add_token(TokenType::LESSEQUAL, "LESS EQUAL".to_string())
+                    self.add_token(TokenType::LESSEQUAL)
                 } else {
-                    self.add_token(TokenType::LESS, "LESS".to_string())
+                    self.add_token(TokenType::LESS)
                 }
             }
             '>' => {
                 if self.matches('=') {
-                    self.add_token(TokenType::GREATEREQUAL, "GREATER EQUAL".to_string())
+                    self.add_token(TokenType::GREATEREQUAL)
                 } else {
-                    self.add_token(TokenType::GREATER, "GREATER".to_string())
+                    self.add_token(TokenType::GREATER)
                 }
             }
             '/' => {
@@ -111,40 +143,67 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                } else if self.matches('*') {
+                    self.block_comment();
                 } else {
-                    self.add_token(TokenType::SLASH, "SLASH".to_string());
+                    self.add_token(TokenType::SLASH);
                 }
             }
             ' ' | '\r' | '\t' => (),
-            '\n' => self.line += 1,
+            '\n' => self.new_line(),
             '"' => self.string(),
-            'o' => {
-                if self.matches('r') {
-                    self.add_token(TokenType::OR, "Boolean OR".to_string());
-                }
-            }
             _ => {
                 if self.is_digit(c) {
                     self.number()
                 } else if self.is_alpha(c) {
                     self.identifier()
                 } else {
-                    self.lox.error(self.line, "Unexpected character")
+                    let span: Span = self.current_span();
+                    self.errors
+                        .push(LoxError::scan(span, "Unexpected character."))
                 }
             }
         };
     }
 
     fn advance(&mut self) -> char {
-        let curr_char: char = self.source.chars().nth(self.current as usize).unwrap();
-        self.current += 1;
+        let curr_char: char = self.peek();
+        self.current += curr_char.len_utf8();
+        self.column += 1;
         curr_char
     }
 
-    fn add_token(&mut self, token_type: TokenType, literal: String) {
-        let text: &str = &self.source[self.start as usize..self.current as usize];
-        self.tokens
-            .push(Token::new(token_type, text.to_string(), literal, self.line))
+    fn add_token(&mut self, token_type: TokenType) {
+        let lexeme: Symbol = Symbol::intern(&self.source[self.start..self.current]);
+        let span: Span = self.current_span();
+        self.tokens.push(Token::new(token_type, lexeme, None, span))
+    }
+
+    // Literal text isn't interned, programs can have any number of distinct
+    // strings and numbers
+    fn add_literal(&mut self, token_type: TokenType, value: Rc<str>) {
+        let span: Span = self.current_span();
+        self.tokens.push(Token::new(
+            token_type,
+            Symbol::intern(""),
+            Some(value),
+            span,
+        ))
+    }
+
+    // Span of the text scanned since the token started
+    fn current_span(&self) -> Span {
+        Span::new(
+            self.start,
+            self.current - self.start,
+            self.start_line,
+            self.start_column,
+        )
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.column = 1;
     }
 
     fn matches(&mut self, expected: char) -> bool {
@@ -152,44 +211,135 @@ impl Scanner {
             return false;
         }
 
-        if self.source.chars().nth(self.current as usize).unwrap() != expected {
+        if self.peek() != expected {
             return false;
         }
 
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\n';
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    // `/* ... */`, which may nest and span lines
+    fn block_comment(&mut self) {
+        let mut depth: u32 = 1;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                let span: Span = self.current_span();
+                self.errors
+                    .push(LoxError::scan(span, "Unterminated block comment."));
+                return;
+            }
+
+            match self.advance() {
+                '\n' => self.new_line(),
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                }
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                }
+                _ => (),
+            }
         }
-        self.source.chars().nth(self.current as usize).unwrap()
     }
 
+    // The literal is built up as the string is read, with escapes replaced by
+    // the characters they stand for. A bad escape is reported and skipped so
+    // the rest of the string is still checked.
     fn string(&mut self) {
+        let mut value: String = String::new();
+
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c: char = self.advance();
+            match c {
+                '\n' => {
+                    self.new_line();
+                    value.push(c);
+                }
+                '\\' => {
+                    if let Some(escaped) = self.escape() {
+                        value.push(escaped);
+                    }
+                }
+                _ => value.push(c),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            self.lox.error(self.line, "Unterminated string");
+            let span: Span = self.current_span();
+            self.errors
+                .push(LoxError::scan(span, "Unterminated string."));
+            return;
         }
 
         self.advance();
+        self.add_literal(TokenType::STRING, Rc::from(value))
+    }
 
-        let start_inc: u32 = self.start + 1;
-        let current_dec: u32 = self.current - 1;
+    // Reads the rest of an escape sequence, the backslash has been consumed
+    fn escape(&mut self) -> Option<char> {
+        // The backslash is a single byte and column
+        let start: usize = self.current - 1;
+        let column: u32 = self.column - 1;
+        let line: u32 = self.line;
 
-        let value: &str = &self.source[start_inc as usize..current_dec as usize];
-        self.add_token(TokenType::STRING, value.to_string())
+        if self.is_at_end() {
+            return None;
+        }
+
+        let escaped: Option<char> = match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            'u' => self.unicode_escape(),
+            c => {
+                if c == '\n' {
+                    self.new_line();
+                }
+                None
+            }
+        };
+
+        if escaped.is_none() {
+            let span: Span = Span::new(start, self.current - start, line, column);
+            let text: &str = &self.source[start..self.current];
+            let message: String = format!("Invalid escape sequence '{}'.", text.trim_end());
+            self.errors.push(LoxError::scan(span, &message));
+        }
+        escaped
+    }
+
+    // `\u{...}` with one to six hex digits naming a Unicode scalar value
+    fn unicode_escape(&mut self) -> Option<char> {
+        if !self.matches('{') {
+            return None;
+        }
+
+        let digits_start: usize = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits: String = self.source[digits_start..self.current].to_string();
+
+        if !self.matches('}') || digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c >= '0' && c <= '9'
+        c.is_ascii_digit()
     }
 
     fn number(&mut self) {
@@ -205,42 +355,43 @@ impl Scanner {
             }
         }
 
-        let num_to_str: &str = &self.source[self.start as usize..self.current as usize];
-        self.add_token(TokenType::NUMBER, num_to_str.to_string())
+        let digits: Rc<str> = Rc::from(&self.source[self.start..self.current]);
+        self.add_literal(TokenType::NUMBER, digits)
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() as u32 {
-            return '\0';
-        }
-        self.source.chars().nth(self.current as usize + 1).unwrap()
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
+    // Maximal munch, the whole word is read before checking for a keyword so
+    // `orchid` or `classy` stay identifiers
     fn identifier(&mut self) {
         while self.is_alpha_numeric(self.peek()) {
             self.advance();
         }
 
-        let text: &str = &self.source[self.start as usize..self.current as usize];
-        let token_type: Option<&TokenType> = self.keywords.get(&text.to_string());
+        let text: &str = &self.source[self.start..self.current];
+        let token_type: Option<&TokenType> = self.keywords.get(text);
         match token_type {
-            Some(TokenType::AND) => self.add_token(TokenType::AND, "Boolean AND".to_string()),
-            Some(TokenType::CLASS) => self.add_token(TokenType::CLASS, "CLASS".to_string()),
-            Some(TokenType::ELSE) => self.add_token(TokenType::ELSE, "ELSE".to_string()),
-            Some(TokenType::FALSE) => self.add_token(TokenType::FALSE, "Boolean False".to_string()),
-            Some(TokenType::FOR) => self.add_token(TokenType::FOR, "FOR".to_string()),
-            Some(TokenType::FUN) => self.add_token(TokenType::FUN, "FUN".to_string()),
-            Some(TokenType::IF) => self.add_token(TokenType::IF, "IF".to_string()),
-            Some(TokenType::NIL) => self.add_token(TokenType::NIL, "NIL".to_string()),
-            Some(TokenType::OR) => self.add_token(TokenType::OR, "Boolean OR".to_string()),
-            Some(TokenType::PRINT) => self.add_token(TokenType::PRINT, "PRINT".to_string()),
-            Some(TokenType::RETURN) => self.add_token(TokenType::RETURN, "RETURN".to_string()),
-            Some(TokenType::SUPER) => self.add_token(TokenType::SUPER, "SUPER".to_string()),
-            Some(TokenType::THIS) => self.add_token(TokenType::THIS, "THIS".to_string()),
-            Some(TokenType::TRUE) => self.add_token(TokenType::TRUE, "Boolean TRUE".to_string()),
-            Some(TokenType::VAR) => self.add_token(TokenType::VAR, "VAR".to_string()),
-            Some(TokenType::WHILE) => self.add_token(TokenType::WHILE, "WHILE".to_string()),
-            _ => self.add_token(TokenType::IDENTIFIER, "Null".to_string()),
+            Some(TokenType::AND) => self.add_token(TokenType::AND),
+            Some(TokenType::CLASS) => self.add_token(TokenType::CLASS),
+            Some(TokenType::ELSE) => self.add_token(TokenType::ELSE),
+            Some(TokenType::FALSE) => self.add_token(TokenType::FALSE),
+            Some(TokenType::FOR) => self.add_token(TokenType::FOR),
+            Some(TokenType::FUN) => self.add_token(TokenType::FUN),
+            Some(TokenType::IF) => self.add_token(TokenType::IF),
+            Some(TokenType::NIL) => self.add_token(TokenType::NIL),
+            Some(TokenType::OR) => self.add_token(TokenType::OR),
+            Some(TokenType::PRINT) => self.add_token(TokenType::PRINT),
+            Some(TokenType::RETURN) => self.add_token(TokenType::RETURN),
+            Some(TokenType::SUPER) => self.add_token(TokenType::SUPER),
+            Some(TokenType::THIS) => self.add_token(TokenType::THIS),
+            Some(TokenType::TRUE) => self.add_token(TokenType::TRUE),
+            Some(TokenType::VAR) => self.add_token(TokenType::VAR),
+            Some(TokenType::WHILE) => self.add_token(TokenType::WHILE),
+            _ => self.add_token(TokenType::IDENTIFIER),
         };
     }
 
@@ -249,6 +400,6 @@ impl Scanner {
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+        c.is_ascii_alphabetic() || c == '_'
     }
 }
@@ -1,20 +1,50 @@
-use crate::{expr::Expr, token::Token};
+use std::rc::Rc;
+
+use crate::{
+    expr::Expr,
+    token::{Span, Token},
+};
 
 #[derive(Debug, Clone)]
 pub enum Stmt {
     Expression(Expression),
     Print(Print),
     Var(Var),
+    Block(Block),
+    If(If),
+    While(While),
+    // Shared so declaring a function doesn't copy its body
+    Function(Rc<Function>),
+    Return(Return),
+    Class(Class),
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expression(Expression { span, .. })
+            | Stmt::Print(Print { span, .. })
+            | Stmt::Var(Var { span, .. })
+            | Stmt::Block(Block { span, .. })
+            | Stmt::If(If { span, .. })
+            | Stmt::While(While { span, .. })
+            | Stmt::Return(Return { span, .. })
+            | Stmt::Class(Class { span, .. }) => *span,
+            Stmt::Function(function) => function.span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Expression {
     pub expression: Expr,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Print {
     pub print_expression: Expr,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -22,4 +52,50 @@ pub struct Var {
     pub name: Token,
     // Initializer should be optional
     pub initializer: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct While {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: Token,
+    // Always an Expr::Variable when present
+    pub superclass: Option<Expr>,
+    pub methods: Vec<Rc<Function>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Return {
+    pub keyword: Token,
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub statements: Vec<Stmt>,
+    pub span: Span,
 }
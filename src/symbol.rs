@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    sync::{Mutex, OnceLock},
+};
+
+// An interned name: identifiers, keywords and punctuation are stored once
+// for the whole process, so comparing or hashing them is comparing a
+// pointer. Literal values are never interned, the table only grows with the
+// distinct names programs use.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+// Names the interpreters use while running, in the table from the start so
+// getting them never takes the lock
+static INIT: &str = "init";
+static THIS: &str = "this";
+static SUPER: &str = "super";
+
+fn interner() -> &'static Mutex<HashMap<&'static str, Symbol>> {
+    static INTERNER: OnceLock<Mutex<HashMap<&'static str, Symbol>>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        let preset: HashMap<&'static str, Symbol> = [INIT, THIS, SUPER]
+            .into_iter()
+            .map(|text| (text, Symbol(text)))
+            .collect();
+        Mutex::new(preset)
+    })
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Symbol {
+        let mut interned = interner().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(symbol) = interned.get(text) {
+            return *symbol;
+        }
+        // Leaked on purpose, symbols live as long as the process
+        let leaked: &'static str = Box::leak(text.to_string().into_boxed_str());
+        interned.insert(leaked, Symbol(leaked));
+        Symbol(leaked)
+    }
+
+    // The symbol for text already interned, without adding it to the table.
+    // For lookups by name from outside Lox, which shouldn't grow the table
+    // every time they miss.
+    pub fn lookup(text: &str) -> Option<Symbol> {
+        let interned = interner().lock().unwrap_or_else(|e| e.into_inner());
+        interned.get(text).copied()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+
+    pub fn init() -> Symbol {
+        Symbol(INIT)
+    }
+
+    pub fn this() -> Symbol {
+        Symbol(THIS)
+    }
+
+    pub fn super_() -> Symbol {
+        Symbol(SUPER)
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state);
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Symbol::intern(text)
+    }
+}
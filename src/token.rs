@@ -1,27 +1,78 @@
-use crate::token_type::TokenType;
+use std::rc::Rc;
+
+use crate::{symbol::Symbol, token_type::TokenType};
+
+// Where a piece of source text lives. Offset and length are in bytes, line
+// and column count from 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub offset: usize,
+    pub length: usize,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    pub fn new(offset: usize, length: usize, line: u32, column: u32) -> Self {
+        Self {
+            offset,
+            length,
+            line,
+            column,
+        }
+    }
+
+    // Span from the start of this one to the end of `end`
+    pub fn to(&self, end: &Span) -> Span {
+        let end_offset: usize = (end.offset + end.length).max(self.offset + self.length);
+        Span::new(
+            self.offset,
+            end_offset - self.offset,
+            self.line,
+            self.column,
+        )
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
-    pub literal: String,
-    pub line: u32,
+    // Interned source text, empty for string and number literals
+    pub lexeme: Symbol,
+    // A string's value with escapes replaced, or a number's digits
+    pub literal: Option<Rc<str>>,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: String, line: u32) -> Self {
+    pub fn new(
+        token_type: TokenType,
+        lexeme: Symbol,
+        literal: Option<Rc<str>>,
+        span: Span,
+    ) -> Self {
         Self {
             token_type,
             lexeme,
             literal,
-            line,
+            span,
         }
     }
 
     pub fn to_string_impl(&self) -> String {
         format!(
-            "type: {}, lexeme: {}, literal: {}",
+            "type: {}, lexeme: {}, literal: {:?}",
             self.token_type, self.lexeme, self.literal
         )
     }
+
+    // The token as written, for messages. Literals aren't interned so their
+    // text is rebuilt from the value.
+    pub fn text(&self) -> String {
+        match (self.token_type, &self.literal) {
+            (TokenType::STRING, Some(value)) => format!("\"{}\"", value),
+            (_, Some(value)) => value.to_string(),
+            _ => self.lexeme.to_string(),
+        }
+    }
 }
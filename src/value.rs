@@ -0,0 +1,102 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    interpreter_objects::{LoxClass, LoxFunction, LoxInstance},
+    native::NativeFunction,
+};
+
+// Everything a Lox expression can evaluate to at runtime
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    // Shared, so reading a string variable or literal doesn't copy the text
+    Str(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+impl Value {
+    // Lox truthiness, only nil and false are falsey
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Bool(val) => *val,
+            _ => true,
+        }
+    }
+}
+
+// How `print` shows a value
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(val) => write!(f, "{}", val),
+            Value::Number(val) => write!(f, "{}", format_number(*val)),
+            Value::Str(val) => write!(f, "{}", val),
+            Value::Function(function) => write!(f, "{:?}", function),
+            Value::Native(native) => write!(f, "{:?}", native),
+            Value::Class(class) => write!(f, "{:?}", class),
+            Value::Instance(instance) => write!(f, "{:?}", instance.borrow()),
+        }
+    }
+}
+
+// Shared with the bytecode VM so both backends print numbers alike
+pub(crate) fn format_number(val: f64) -> String {
+    // f64 already drops the `.0` of integers, only the infinities need
+    // spelling out the way Lox does
+    if val.is_infinite() {
+        let sign: &str = if val < 0.0 { "-" } else { "" };
+        format!("{}Infinity", sign)
+    } else {
+        format!("{}", val)
+    }
+}
+
+// Values of different types are never equal, and functions, classes and
+// instances are only equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Native(l), Value::Native(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+// So native functions can return plain Rust values with `.into()`
+impl From<f64> for Value {
+    fn from(val: f64) -> Self {
+        Value::Number(val)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Self {
+        Value::Bool(val)
+    }
+}
+
+impl From<String> for Value {
+    fn from(val: String) -> Self {
+        Value::Str(val.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(val: &str) -> Self {
+        Value::Str(val.into())
+    }
+}
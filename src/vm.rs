@@ -0,0 +1,762 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use crate::{
+    bytecode_file,
+    chunk::{Constant, FunctionProto, OpCode},
+    compiler::Compiler,
+    disassembler::disassemble_instruction,
+    error::LoxError,
+    lox::{parse_program, parse_program_from},
+    native::{self, NativeArgs, NativeFunction, NativeResult},
+    object::{
+        GcStats, Heap, Obj, ObjBoundMethod, ObjClass, ObjClosure, ObjInstance, ObjRef, ObjUpvalue,
+        VmValue,
+    },
+    stmt::Stmt,
+    token::Span,
+    value::Value,
+};
+
+// Deep enough for any sensible recursion, shallow enough to report runaway
+// recursion as a Lox error instead of exhausting memory
+const FRAMES_MAX: usize = 1024;
+
+// A function call in progress
+struct CallFrame {
+    closure: ObjRef,
+    function: Rc<FunctionProto>,
+    ip: usize,
+    // Stack index of slot 0, the callee or receiver
+    base: usize,
+}
+
+// Stack based bytecode VM, an alternative backend to the tree-walking
+// interpreter. Globals persist between runs like they do for `Lox`. Its
+// heap is garbage collected, so unlike with `Lox` scripts that build
+// reference cycles don't leak.
+pub struct Vm {
+    heap: Heap,
+    stack: Vec<VmValue>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<ObjRef, VmValue>,
+    // Upvalues still pointing into the stack, closed when their slot goes away
+    open_upvalues: Vec<ObjRef>,
+    init_string: ObjRef,
+    output: Box<dyn Write>,
+    // Where the stack and each instruction go before executing, when tracing
+    trace: Option<Box<dyn Write>>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let mut heap: Heap = Heap::new();
+        let init_string: ObjRef = heap.intern("init");
+        let mut vm: Vm = Self {
+            heap,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            init_string,
+            output,
+            trace: None,
+        };
+        vm.define_native("clock", 0, native::clock);
+        vm
+    }
+
+    // Same contract as `Lox::define_native`
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&NativeArgs) -> NativeResult + 'static,
+    {
+        let native: ObjRef =
+            self.alloc(Obj::Native(Rc::new(NativeFunction::new(name, arity, body))));
+        // Kept on the stack so interning the name can't collect it
+        self.push(VmValue::Obj(native));
+        let name_ref: ObjRef = self.intern(name);
+        self.globals.insert(name_ref, VmValue::Obj(native));
+        self.pop();
+    }
+
+    // How much the heap may grow past the live data before the next
+    // collection, at least 1.0
+    pub fn set_gc_growth_factor(&mut self, growth_factor: f64) {
+        self.heap.set_growth_factor(growth_factor);
+    }
+
+    // Collects before every allocation, slow but exposes missing roots
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.heap.set_stress(stress);
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    // Traces execution to `trace`, or stops tracing with None
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write>>) {
+        self.trace = trace;
+    }
+
+    pub fn run(&mut self, source: &str) -> Result<(), Vec<LoxError>> {
+        self.run_from(source, 0)
+    }
+
+    // Same contract as `Lox::run_from`
+    pub fn run_from(&mut self, source: &str, start: usize) -> Result<(), Vec<LoxError>> {
+        let (statements, _) = parse_program_from(source, start)?;
+        let script: Rc<FunctionProto> = Compiler::compile(&statements)?;
+        self.interpret(script)
+            .map(|_| ())
+            .map_err(|error| vec![error])
+    }
+
+    // Same contract as `Lox::eval`. Only values a native could return come
+    // back, anything else living in the heap is an error.
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        let (statements, _) = parse_program(source).map_err(|mut errors| errors.remove(0))?;
+        let script: Rc<FunctionProto> =
+            Compiler::compile(&statements).map_err(|mut errors| errors.remove(0))?;
+        let value: VmValue = self.interpret(script)?;
+        self.to_host(value).ok_or_else(|| {
+            let span: Span = statements.last().map_or(Span::new(0, 0, 1, 1), Stmt::span);
+            LoxError::runtime_at(
+                span,
+                "Only numbers, strings, booleans and nil can be returned to the host.",
+            )
+        })
+    }
+
+    // None when the global isn't defined or holds a heap object other than
+    // a string
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        let name_ref: ObjRef = self.heap.find_string(name)?;
+        let value: VmValue = *self.globals.get(&name_ref)?;
+        self.to_host(value)
+    }
+
+    // Runs a script compiled with `rlox::compile`, skipping the front end
+    pub fn run_compiled(&mut self, bytes: &[u8]) -> Result<(), LoxError> {
+        let script: Rc<FunctionProto> = bytecode_file::decode(bytes)?;
+        self.interpret(script).map(|_| ())
+    }
+
+    // Runs the script to the end, giving back what it returned
+    fn interpret(&mut self, script: Rc<FunctionProto>) -> Result<VmValue, LoxError> {
+        let closure: ObjRef = self.alloc(Obj::Closure(ObjClosure {
+            function: script,
+            upvalues: Vec::new(),
+        }));
+        self.stack.push(VmValue::Obj(closure));
+        let result: Result<VmValue, LoxError> =
+            self.call_closure(closure, 0).and_then(|_| self.execute());
+        if result.is_err() {
+            // Closures that escaped into globals keep what they captured
+            self.close_upvalues(0);
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    // Every allocation goes through here, so a collection only ever happens
+    // while everything in use is reachable from the roots
+    fn alloc(&mut self, obj: Obj) -> ObjRef {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.alloc(obj)
+    }
+
+    fn intern(&mut self, text: &str) -> ObjRef {
+        if let Some(existing) = self.heap.find_string(text) {
+            return existing;
+        }
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.intern(text)
+    }
+
+    // Roots are the value stack, the closures being run, globals, upvalues
+    // still pointing into the stack and the interned "init"
+    fn collect_garbage(&mut self) {
+        for value in &self.stack {
+            self.heap.mark_value(*value);
+        }
+        for frame in &self.frames {
+            self.heap.mark_object(frame.closure);
+        }
+        for (name, value) in &self.globals {
+            self.heap.mark_object(*name);
+            self.heap.mark_value(*value);
+        }
+        for upvalue in &self.open_upvalues {
+            self.heap.mark_object(*upvalue);
+        }
+        self.heap.mark_object(self.init_string);
+
+        self.heap.trace();
+        self.heap.sweep();
+    }
+
+    fn frame(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("VM has a call frame")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame: &mut CallFrame = self.frame();
+        let byte: u8 = frame.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame: &mut CallFrame = self.frame();
+        let value: u16 = frame.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Constant {
+        let index: u16 = self.read_u16();
+        self.frame().function.chunk.constants[index as usize].clone()
+    }
+
+    // A name operand, interned so it can key globals, fields and methods
+    fn read_string(&mut self) -> ObjRef {
+        match self.read_constant() {
+            Constant::Str(text) => self.intern(&text),
+            _ => panic!("Expected a string constant"),
+        }
+    }
+
+    fn push(&mut self, value: VmValue) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> VmValue {
+        self.stack.pop().expect("VM stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> VmValue {
+        self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // Located at the instruction being executed
+    fn error(&mut self, message: &str) -> LoxError {
+        let frame: &mut CallFrame = self.frame();
+        let span: Span = frame.function.chunk.span_at(frame.ip.saturating_sub(1));
+        LoxError::runtime_at(span, message)
+    }
+
+    fn execute(&mut self) -> Result<VmValue, LoxError> {
+        loop {
+            if self.trace.is_some() {
+                self.trace_instruction()?;
+            }
+            let byte: u8 = self.read_byte();
+            let op: OpCode = match OpCode::from_byte(byte) {
+                Some(op) => op,
+                None => return Err(self.error(&format!("Unknown opcode {}.", byte))),
+            };
+
+            match op {
+                OpCode::Constant => {
+                    let value: VmValue = match self.read_constant() {
+                        Constant::Number(val) => VmValue::Number(val),
+                        Constant::Str(text) => VmValue::Obj(self.intern(&text)),
+                        Constant::Function(_) => {
+                            return Err(self.error("Functions can only be loaded as closures."))
+                        }
+                    };
+                    self.push(value);
+                }
+                OpCode::Nil => self.push(VmValue::Nil),
+                OpCode::True => self.push(VmValue::Bool(true)),
+                OpCode::False => self.push(VmValue::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot: usize = self.read_byte() as usize;
+                    let base: usize = self.frame().base;
+                    self.push(self.stack[base + slot]);
+                }
+                OpCode::SetLocal => {
+                    let slot: usize = self.read_byte() as usize;
+                    let base: usize = self.frame().base;
+                    self.stack[base + slot] = self.peek(0);
+                }
+                OpCode::GetGlobal => {
+                    let name: ObjRef = self.read_string();
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(*value),
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name: ObjRef = self.read_string();
+                    let value: VmValue = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name: ObjRef = self.read_string();
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.undefined_variable(name));
+                    }
+                    self.globals.insert(name, self.peek(0));
+                }
+                OpCode::GetUpvalue => {
+                    let index: usize = self.read_byte() as usize;
+                    let upvalue: ObjRef = self.current_upvalue(index);
+                    let value: VmValue = match self.heap.get(upvalue) {
+                        Obj::Upvalue(ObjUpvalue::Open(slot)) => self.stack[*slot],
+                        Obj::Upvalue(ObjUpvalue::Closed(value)) => *value,
+                        _ => panic!("Expected an upvalue object"),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index: usize = self.read_byte() as usize;
+                    let upvalue: ObjRef = self.current_upvalue(index);
+                    let value: VmValue = self.peek(0);
+                    match self.heap.get_mut(upvalue) {
+                        Obj::Upvalue(ObjUpvalue::Open(slot)) => {
+                            let slot: usize = *slot;
+                            self.stack[slot] = value;
+                        }
+                        Obj::Upvalue(closed) => *closed = ObjUpvalue::Closed(value),
+                        _ => panic!("Expected an upvalue object"),
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name: ObjRef = self.read_string();
+                    let instance: ObjRef = match self.peek(0) {
+                        VmValue::Obj(reference)
+                            if matches!(self.heap.get(reference), Obj::Instance(_)) =>
+                        {
+                            reference
+                        }
+                        _ => return Err(self.error("Only instances have properties.")),
+                    };
+                    let (field, class): (Option<VmValue>, ObjRef) = match self.heap.get(instance) {
+                        Obj::Instance(instance) => {
+                            (instance.fields.get(&name).copied(), instance.class)
+                        }
+                        _ => unreachable!(),
+                    };
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.push(value);
+                        }
+                        None => self.bind_method(class, name)?,
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name: ObjRef = self.read_string();
+                    let value: VmValue = self.peek(0);
+                    match self.peek(1) {
+                        VmValue::Obj(reference) => match self.heap.get_mut(reference) {
+                            Obj::Instance(instance) => {
+                                instance.fields.insert(name, value);
+                            }
+                            _ => return Err(self.error("Only instances have fields.")),
+                        },
+                        _ => return Err(self.error("Only instances have fields.")),
+                    }
+                    self.pop();
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name: ObjRef = self.read_string();
+                    let superclass: ObjRef = match self.pop() {
+                        VmValue::Obj(reference) => reference,
+                        _ => return Err(self.error("Superclass must be a class.")),
+                    };
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let b: VmValue = self.pop();
+                    let a: VmValue = self.pop();
+                    self.push(VmValue::Bool(a == b));
+                }
+                OpCode::Greater => self.compare(|a, b| a > b)?,
+                OpCode::GreaterEqual => self.compare(|a, b| a >= b)?,
+                OpCode::Less => self.compare(|a, b| a < b)?,
+                OpCode::LessEqual => self.compare(|a, b| a <= b)?,
+                OpCode::Add => self.add()?,
+                OpCode::Subtract => self.arithmetic(|a, b| a - b)?,
+                OpCode::Multiply => self.arithmetic(|a, b| a * b)?,
+                OpCode::Divide => self.arithmetic(|a, b| a / b)?,
+                OpCode::Not => {
+                    let value: VmValue = self.pop();
+                    self.push(VmValue::Bool(value.is_falsey()));
+                }
+                OpCode::Negate => match self.peek(0) {
+                    VmValue::Number(val) => {
+                        self.pop();
+                        self.push(VmValue::Number(-val));
+                    }
+                    _ => return Err(self.error("Operand must be a number.")),
+                },
+                OpCode::Print => {
+                    let value: VmValue = self.pop();
+                    let text: String = self.heap.format(value);
+                    writeln!(self.output, "{}", text)
+                        .map_err(|e| LoxError::io(&format!("Failed to write output: {}", e)))?;
+                }
+                OpCode::Jump => {
+                    let offset: u16 = self.read_u16();
+                    self.frame().ip += offset as usize;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset: u16 = self.read_u16();
+                    if self.peek(0).is_falsey() {
+                        self.frame().ip += offset as usize;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset: u16 = self.read_u16();
+                    self.frame().ip -= offset as usize;
+                }
+                OpCode::Call => {
+                    let arg_count: usize = self.read_byte() as usize;
+                    self.call_value(self.peek(arg_count), arg_count)?;
+                }
+                OpCode::Closure => {
+                    let function: Rc<FunctionProto> = match self.read_constant() {
+                        Constant::Function(function) => function,
+                        _ => return Err(self.error("Expected a function constant.")),
+                    };
+                    let mut upvalues: Vec<ObjRef> = Vec::new();
+                    for _ in 0..function.upvalue_count {
+                        let is_local: bool = self.read_byte() == 1;
+                        let index: usize = self.read_byte() as usize;
+                        if is_local {
+                            let slot: usize = self.frame().base + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        } else {
+                            upvalues.push(self.current_upvalue(index));
+                        }
+                    }
+                    let closure: ObjRef =
+                        self.alloc(Obj::Closure(ObjClosure { function, upvalues }));
+                    self.push(VmValue::Obj(closure));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result: VmValue = self.pop();
+                    let frame: CallFrame = self.frames.pop().expect("VM has a call frame");
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name: ObjRef = self.read_string();
+                    let class: ObjRef = self.alloc(Obj::Class(ObjClass {
+                        name: self.heap.string(name).to_string(),
+                        methods: HashMap::new(),
+                    }));
+                    self.push(VmValue::Obj(class));
+                }
+                OpCode::Inherit => {
+                    let methods: HashMap<ObjRef, ObjRef> = match self.peek(1) {
+                        VmValue::Obj(reference) => match self.heap.get(reference) {
+                            Obj::Class(superclass) => superclass.methods.clone(),
+                            _ => return Err(self.error("Superclass must be a class.")),
+                        },
+                        _ => return Err(self.error("Superclass must be a class.")),
+                    };
+                    // The compiler always puts a class here, a hand-made
+                    // .loxc file might not
+                    match self.peek(0) {
+                        VmValue::Obj(reference) => match self.heap.get_mut(reference) {
+                            Obj::Class(subclass) => subclass.methods.extend(methods),
+                            _ => return Err(self.error("Only classes can inherit.")),
+                        },
+                        _ => return Err(self.error("Only classes can inherit.")),
+                    }
+                    self.pop();
+                }
+                OpCode::Method => {
+                    let name: ObjRef = self.read_string();
+                    // Calls assume every method is a closure
+                    let method: ObjRef = match self.peek(0) {
+                        VmValue::Obj(reference)
+                            if matches!(self.heap.get(reference), Obj::Closure(_)) =>
+                        {
+                            reference
+                        }
+                        _ => return Err(self.error("Methods must be functions.")),
+                    };
+                    match self.peek(1) {
+                        VmValue::Obj(reference) => match self.heap.get_mut(reference) {
+                            Obj::Class(class) => {
+                                class.methods.insert(name, method);
+                            }
+                            _ => return Err(self.error("Methods can only be added to classes.")),
+                        },
+                        _ => return Err(self.error("Methods can only be added to classes.")),
+                    }
+                    self.pop();
+                }
+            }
+        }
+    }
+
+    // Writes the value stack, then the instruction about to run:
+    //           [ <script> ][ 1 ][ 2 ]
+    // 0006    | Add
+    fn trace_instruction(&mut self) -> Result<(), LoxError> {
+        let mut line: String = " ".repeat(10);
+        for value in &self.stack {
+            line.push_str(&format!("[ {} ]", self.heap.format(*value)));
+        }
+        line.push('\n');
+        let frame: &CallFrame = self.frames.last().expect("VM has a call frame");
+        disassemble_instruction(&frame.function.chunk, frame.ip, &mut line);
+
+        if let Some(trace) = &mut self.trace {
+            trace
+                .write_all(line.as_bytes())
+                .map_err(|e| LoxError::io(&format!("Failed to write trace: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    fn undefined_variable(&mut self, name: ObjRef) -> LoxError {
+        let message: String = format!("Undefined variable '{}'.", self.heap.string(name));
+        self.error(&message)
+    }
+
+    fn current_upvalue(&mut self, index: usize) -> ObjRef {
+        let closure: ObjRef = self.frame().closure;
+        self.heap.closure(closure).upvalues[index]
+    }
+
+    fn compare(&mut self, op: fn(f64, f64) -> bool) -> Result<(), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (VmValue::Number(a), VmValue::Number(b)) => {
+                self.pop();
+                self.pop();
+                self.push(VmValue::Bool(op(a, b)));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be numbers.")),
+        }
+    }
+
+    fn arithmetic(&mut self, op: fn(f64, f64) -> f64) -> Result<(), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (VmValue::Number(a), VmValue::Number(b)) => {
+                self.pop();
+                self.pop();
+                self.push(VmValue::Number(op(a, b)));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be numbers.")),
+        }
+    }
+
+    fn add(&mut self) -> Result<(), LoxError> {
+        let (a, b) = (self.peek(1), self.peek(0));
+        let result: VmValue = match (a, b) {
+            (VmValue::Number(a), VmValue::Number(b)) => VmValue::Number(a + b),
+            (VmValue::Obj(a), VmValue::Obj(b)) => match (self.heap.get(a), self.heap.get(b)) {
+                (Obj::Str(a), Obj::Str(b)) => {
+                    let joined: String = format!("{}{}", a, b);
+                    VmValue::Obj(self.intern(&joined))
+                }
+                _ => return Err(self.error("Operands must be two numbers or two strings.")),
+            },
+            _ => return Err(self.error("Operands must be two numbers or two strings.")),
+        };
+        self.pop();
+        self.pop();
+        self.push(result);
+        Ok(())
+    }
+
+    fn call_value(&mut self, callee: VmValue, arg_count: usize) -> Result<(), LoxError> {
+        let reference: ObjRef = match callee {
+            VmValue::Obj(reference) => reference,
+            _ => return Err(self.error("Can only call functions and classes.")),
+        };
+        let callee_slot: usize = self.stack.len() - arg_count - 1;
+
+        match self.heap.get(reference) {
+            Obj::Closure(_) => self.call_closure(reference, arg_count),
+            Obj::BoundMethod(bound) => {
+                let (receiver, method) = (bound.receiver, bound.method);
+                self.stack[callee_slot] = receiver;
+                self.call_closure(method, arg_count)
+            }
+            Obj::Class(class) => {
+                let initializer: Option<ObjRef> = class.methods.get(&self.init_string).copied();
+                let instance: ObjRef = self.alloc(Obj::Instance(ObjInstance {
+                    class: reference,
+                    fields: HashMap::new(),
+                }));
+                self.stack[callee_slot] = VmValue::Obj(instance);
+                match initializer {
+                    Some(initializer) => self.call_closure(initializer, arg_count),
+                    None if arg_count != 0 => {
+                        Err(self.error(&format!("Expected 0 arguments but got {}.", arg_count)))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Obj::Native(native) => {
+                let native: Rc<NativeFunction> = Rc::clone(native);
+                self.call_native(&native, arg_count)
+            }
+            _ => Err(self.error("Can only call functions and classes.")),
+        }
+    }
+
+    fn call_closure(&mut self, closure: ObjRef, arg_count: usize) -> Result<(), LoxError> {
+        let function: Rc<FunctionProto> = Rc::clone(&self.heap.closure(closure).function);
+        if arg_count != function.arity as usize {
+            return Err(self.error(&format!(
+                "Expected {} arguments but got {}.",
+                function.arity, arg_count
+            )));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error("Stack overflow."));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            base: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    // Natives and the host work on tree-walker values, so only primitives
+    // and strings cross the boundary
+    fn to_host(&self, value: VmValue) -> Option<Value> {
+        match value {
+            VmValue::Nil => Some(Value::Nil),
+            VmValue::Bool(val) => Some(Value::Bool(val)),
+            VmValue::Number(val) => Some(Value::Number(val)),
+            VmValue::Obj(reference) => match self.heap.get(reference) {
+                Obj::Str(text) => Some(Value::Str(text.as_str().into())),
+                _ => None,
+            },
+        }
+    }
+
+    fn call_native(&mut self, native: &NativeFunction, arg_count: usize) -> Result<(), LoxError> {
+        if arg_count != native.arity {
+            return Err(self.error(&format!(
+                "Expected {} arguments but got {}.",
+                native.arity, arg_count
+            )));
+        }
+
+        let first: usize = self.stack.len() - arg_count;
+        let mut arguments: Vec<Value> = Vec::with_capacity(arg_count);
+        for index in first..self.stack.len() {
+            match self.to_host(self.stack[index]) {
+                Some(argument) => arguments.push(argument),
+                None => {
+                    return Err(self
+                        .error("Native functions only take numbers, strings, booleans and nil."))
+                }
+            }
+        }
+
+        let result: VmValue = match native.call(&arguments) {
+            Ok(Value::Nil) => VmValue::Nil,
+            Ok(Value::Bool(val)) => VmValue::Bool(val),
+            Ok(Value::Number(val)) => VmValue::Number(val),
+            Ok(Value::Str(text)) => VmValue::Obj(self.intern(&text)),
+            Ok(_) => {
+                return Err(self
+                    .error("Native functions can only return numbers, strings, booleans and nil."))
+            }
+            Err(message) => return Err(self.error(&message)),
+        };
+        self.stack.truncate(first - 1);
+        self.push(result);
+        Ok(())
+    }
+
+    // Replaces the receiver on top of the stack with its method bound to it
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), LoxError> {
+        let method: Option<ObjRef> = match self.heap.get(class) {
+            Obj::Class(class) => class.methods.get(&name).copied(),
+            _ => None,
+        };
+        let method: ObjRef = match method {
+            Some(method) => method,
+            None => {
+                let message: String = format!("Undefined property '{}'.", self.heap.string(name));
+                return Err(self.error(&message));
+            }
+        };
+        // The receiver stays on the stack until the bound method holds it
+        let receiver: VmValue = self.peek(0);
+        let bound: ObjRef = self.alloc(Obj::BoundMethod(ObjBoundMethod { receiver, method }));
+        self.pop();
+        self.push(VmValue::Obj(bound));
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef {
+        for upvalue in &self.open_upvalues {
+            if let Obj::Upvalue(ObjUpvalue::Open(open_slot)) = self.heap.get(*upvalue) {
+                if *open_slot == slot {
+                    return *upvalue;
+                }
+            }
+        }
+        let upvalue: ObjRef = self.alloc(Obj::Upvalue(ObjUpvalue::Open(slot)));
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    // Moves every captured variable at or above `last` off the stack
+    fn close_upvalues(&mut self, last: usize) {
+        let stack: &Vec<VmValue> = &self.stack;
+        let heap: &mut Heap = &mut self.heap;
+        self.open_upvalues
+            .retain(|upvalue| match heap.get_mut(*upvalue) {
+                Obj::Upvalue(state) => match *state {
+                    ObjUpvalue::Open(slot) if slot >= last => {
+                        *state = ObjUpvalue::Closed(stack[slot]);
+                        false
+                    }
+                    _ => true,
+                },
+                _ => false,
+            });
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
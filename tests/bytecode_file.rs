@@ -0,0 +1,249 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+use rlox::{LoxError, Vm};
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("rlox_loxc_{}", name))
+}
+
+fn rlox(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--plain")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+// Same CRC-32 as the writer, for crafting files that pass the checksum
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask: u32 = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn reseal(bytes: &mut Vec<u8>) {
+    let body_length: usize = bytes.len() - 4;
+    let checksum: u32 = crc32(&bytes[..body_length]);
+    bytes.truncate(body_length);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+}
+
+// Where the script's code starts: magic, version, empty name, arity,
+// upvalue count and code length come first
+const FIRST_OP: usize = 4 + 2 + 4 + 1 + 1 + 4;
+
+fn load_error(bytes: &[u8]) -> LoxError {
+    Vm::new().run_compiled(bytes).unwrap_err()
+}
+
+#[test]
+fn compiled_script_runs_like_the_source() {
+    let source: &str = r#"
+        class Object {
+            greet(name) { return "hi " + name; }
+        }
+        fun makeCounter() {
+            var i = 0;
+            fun count() { i = i + 1; return i; }
+            return count;
+        }
+        var counter = makeCounter();
+        counter();
+        print counter();
+        class Greeter < Object {
+            greet(name) { return super.greet(name) + "!"; }
+        }
+        print Greeter().greet("ünïcode");
+        print 0.5 * 3;
+    "#;
+    let script: PathBuf = temp_path("roundtrip.lox");
+    let compiled: PathBuf = temp_path("roundtrip.loxc");
+    fs::write(&script, source).unwrap();
+
+    let output = rlox(&[
+        "compile",
+        script.to_str().unwrap(),
+        "-o",
+        compiled.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(rlox::is_compiled(&fs::read(&compiled).unwrap()));
+
+    let from_source = rlox(&[script.to_str().unwrap()]);
+    let from_compiled = rlox(&["run", compiled.to_str().unwrap()]);
+    assert!(from_compiled.status.success(), "{:?}", from_compiled);
+    let printed: String = String::from_utf8(from_compiled.stdout).unwrap();
+    assert_eq!(printed, "2\nhi ünïcode!\n1.5\n");
+    assert_eq!(printed, String::from_utf8(from_source.stdout).unwrap());
+}
+
+#[test]
+fn runtime_errors_keep_their_location() {
+    let compiled: PathBuf = temp_path("runtime_error.loxc");
+    fs::write(&compiled, rlox::compile("print 1;\nprint -\"a\";").unwrap()).unwrap();
+
+    let output = rlox(&["run", compiled.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(70));
+    let stderr: String = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("runtime error: Operand must be a number."),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("runtime_error.loxc:2:7"), "{}", stderr);
+}
+
+#[test]
+fn compile_errors_are_reported_and_nothing_is_written() {
+    let script: PathBuf = temp_path("broken.lox");
+    let compiled: PathBuf = temp_path("broken.loxc");
+    fs::write(&script, "print ;").unwrap();
+    let _ = fs::remove_file(&compiled);
+
+    let output = rlox(&[
+        "compile",
+        script.to_str().unwrap(),
+        "-o",
+        compiled.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("error: Expect expression."));
+    assert!(!compiled.exists());
+}
+
+#[test]
+fn rejects_files_that_are_not_compiled_lox() {
+    let error: LoxError = load_error(b"print 1;");
+    assert_eq!(
+        error.message(),
+        "Not a compiled Lox file (bad magic number)."
+    );
+    assert!(!error.is_runtime());
+}
+
+#[test]
+fn rejects_other_format_versions() {
+    let mut bytes: Vec<u8> = rlox::compile("print 1;").unwrap();
+    let newer: u16 = rlox::FORMAT_VERSION + 1;
+    bytes[4..6].copy_from_slice(&newer.to_le_bytes());
+
+    let error: LoxError = load_error(&bytes);
+    assert_eq!(
+        error.message(),
+        format!(
+            "Compiled with bytecode format version {}, but this rlox reads version {}. Recompile the script.",
+            newer,
+            rlox::FORMAT_VERSION
+        )
+    );
+}
+
+#[test]
+fn detects_corrupt_and_truncated_files() {
+    let bytes: Vec<u8> = rlox::compile("var a = \"hello\"; print a;").unwrap();
+
+    let mut flipped: Vec<u8> = bytes.clone();
+    flipped[12] ^= 0x40;
+    assert_eq!(
+        load_error(&flipped).message(),
+        "Checksum mismatch, the compiled file is corrupt or truncated."
+    );
+
+    let truncated: &[u8] = &bytes[..bytes.len() / 2];
+    assert_eq!(
+        load_error(truncated).message(),
+        "Checksum mismatch, the compiled file is corrupt or truncated."
+    );
+
+    // Cut short but resealed, so only the reader notices
+    let mut short: Vec<u8> = bytes[..bytes.len() - 10].to_vec();
+    reseal(&mut short);
+    assert_eq!(load_error(&short).message(), "Compiled file is truncated.");
+}
+
+#[test]
+fn rejects_malformed_bytecode_with_a_valid_checksum() {
+    let mut bytes: Vec<u8> = rlox::compile("print 1;").unwrap();
+    bytes[FIRST_OP] = 0xEE;
+    reseal(&mut bytes);
+
+    assert_eq!(
+        load_error(&bytes).message(),
+        "Malformed bytecode in <script> at offset 0: unknown opcode."
+    );
+}
+
+// Valid bytecode that would pop past the frame or read a missing local
+#[test]
+fn rejects_bytecode_that_misuses_the_stack() {
+    // Constant 0, Print, Nil, Return with the Nil turned into a Pop, so the
+    // return has nothing left to pop
+    let mut bytes: Vec<u8> = rlox::compile("print 1;").unwrap();
+    bytes[FIRST_OP + 4] = 4;
+    reseal(&mut bytes);
+    assert_eq!(
+        load_error(&bytes).message(),
+        "Malformed bytecode in <script> at offset 5: stack underflow."
+    );
+
+    // Constant 0, GetLocal 1, ... reading slot 9 instead
+    let mut bytes: Vec<u8> = rlox::compile("{ var a = 1; print a; }").unwrap();
+    bytes[FIRST_OP + 4] = 9;
+    reseal(&mut bytes);
+    assert_eq!(
+        load_error(&bytes).message(),
+        "Malformed bytecode in <script> at offset 3: bad local slot."
+    );
+}
+
+// Well formed bytecode putting the wrong kind of value where the compiler
+// never would fails at runtime instead of crashing the VM
+#[test]
+fn wrong_value_kinds_are_runtime_errors() {
+    // Closure 2 before Method 'm' turned into Constant 0, the string "A"
+    let mut bytes: Vec<u8> = rlox::compile("class A { m() {} } A().m();").unwrap();
+    bytes[FIRST_OP + 9..FIRST_OP + 12].copy_from_slice(&[0, 0, 0]);
+    reseal(&mut bytes);
+    let error: LoxError = load_error(&bytes);
+    assert_eq!(error.message(), "Methods must be functions.");
+    assert!(error.is_runtime());
+
+    // The subclass loaded for Inherit turned into Constant 1, the string "B"
+    let mut bytes: Vec<u8> = rlox::compile("class A {} class B < A {}").unwrap();
+    bytes[FIRST_OP + 19..FIRST_OP + 22].copy_from_slice(&[0, 0, 1]);
+    reseal(&mut bytes);
+    assert_eq!(load_error(&bytes).message(), "Only classes can inherit.");
+}
+
+#[test]
+fn compiler_output_passes_verification() {
+    let dir: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    for entry in fs::read_dir(dir).unwrap() {
+        let source: String = fs::read_to_string(entry.unwrap().path()).unwrap();
+        if let Ok(bytes) = rlox::compile(&source) {
+            if let Err(error) = rlox::disassemble_compiled(&bytes) {
+                panic!("{}\n{}", error, source);
+            }
+        }
+    }
+}
+
+#[test]
+fn disassembles_compiled_files() {
+    let bytes: Vec<u8> = rlox::compile("print 1 + 2;").unwrap();
+    let listing: String = rlox::disassemble_compiled(&bytes).unwrap();
+    assert!(
+        listing.starts_with("== <script> ==\n0000    1 Constant"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("| Add"), "{}", listing);
+}
@@ -0,0 +1,79 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--plain")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn missing_script_is_an_io_error() {
+    for args in [
+        &["no_such_script.lox"][..],
+        &["run", "no_such_script.lox"][..],
+        &["compile", "no_such_script.lox"][..],
+    ] {
+        let output: Output = rlox(args);
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert_eq!(output.status.code(), Some(74), "{:?}", args);
+        assert!(
+            stderr.contains("Could not read 'no_such_script.lox'"),
+            "{}",
+            stderr
+        );
+        assert!(!stderr.contains("panicked"), "{}", stderr);
+    }
+}
+
+#[test]
+fn bad_invocations_exit_with_usage() {
+    for args in [
+        &["--bogus"][..],
+        &["--gc-growth=0.5"][..],
+        &["run"][..],
+        &["compile"][..],
+        &["one.lox", "two.lox"][..],
+    ] {
+        let output: Output = rlox(args);
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert_eq!(output.status.code(), Some(64), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+        assert!(stderr.contains("Usage: rlox"), "{}", stderr);
+    }
+}
+
+// A function failing on a later line is shown against the line defining it
+#[test]
+fn repl_errors_quote_the_line_they_come_from() {
+    let input: &str = "fun f() { return -\"a\"; }\nvar padding = \"xxxxxxxxxxxxxxxx\"; f();\n";
+    for backend in [&[][..], &["--vm"][..]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg("--plain")
+            .args(backend)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output: Output = child.wait_with_output().unwrap();
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert!(stderr.contains("<repl>:1:18"), "{:?}: {}", backend, stderr);
+        assert!(
+            stderr.contains("1 | fun f() { return -\"a\"; }\n  |                  ^\n"),
+            "{:?}: {}",
+            backend,
+            stderr
+        );
+    }
+}
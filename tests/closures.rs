@@ -0,0 +1,64 @@
+mod common;
+
+use common::run;
+
+#[test]
+fn captured_variable_survives_outer_call() {
+    let printed: Vec<String> = run(r#"
+        fun makeCounter() {
+            var i = 0;
+            fun c() {
+                i = i + 1;
+                return i;
+            }
+            return c;
+        }
+
+        var counter = makeCounter();
+        print counter();
+        print counter();
+        "#);
+
+    assert_eq!(printed, vec!["1", "2"]);
+}
+
+#[test]
+fn counters_do_not_share_state() {
+    let printed: Vec<String> = run(r#"
+        fun makeCounter() {
+            var i = 0;
+            fun c() {
+                i = i + 1;
+                return i;
+            }
+            return c;
+        }
+
+        var a = makeCounter();
+        var b = makeCounter();
+        print a();
+        print a();
+        print b();
+        "#);
+
+    assert_eq!(printed, vec!["1", "2", "1"]);
+}
+
+#[test]
+fn closure_reads_declaring_scope_not_caller_scope() {
+    let printed: Vec<String> = run(r#"
+        var x = 1;
+        fun show() {
+            return x;
+        }
+
+        fun caller() {
+            var x = 2;
+            return show();
+        }
+
+        print caller();
+        "#);
+
+    assert_eq!(printed, vec!["1"]);
+}
@@ -0,0 +1,10 @@
+use rlox::Lox;
+
+// Runs the script on a fresh interpreter and returns the lines it printed
+pub fn run(source: &str) -> Vec<String> {
+    let mut lox = Lox::with_captured_output();
+    if let Err(errors) = lox.run(source) {
+        panic!("{}: {}", source, errors[0]);
+    }
+    lox.take_output().lines().map(str::to_string).collect()
+}
@@ -0,0 +1,134 @@
+use std::{fs, path::PathBuf, process::Command};
+
+// Every script in tests/corpus is run on both backends. Expectations are
+// comments in the script itself:
+//
+//   print 1 + 2; // expect: 3
+//   x();         // expect runtime error: Undefined variable 'x'.
+//   var = 1;     // expect error: Expect variable name.
+//
+// Each error must be reported on the line of its comment. A script may
+// expect several errors, as long as they are all found in the same phase.
+struct Expectation {
+    output: Vec<String>,
+    // Exit code when the script should fail
+    exit_code: Option<i32>,
+    // Message on stderr and the line it points at
+    errors: Vec<(String, usize)>,
+}
+
+fn expectation(source: &str) -> Expectation {
+    let mut expected: Expectation = Expectation {
+        output: Vec::new(),
+        exit_code: None,
+        errors: Vec::new(),
+    };
+    for (index, line) in source.lines().enumerate() {
+        if let Some((_, value)) = line.split_once("// expect: ") {
+            expected.output.push(value.to_string());
+        } else if let Some((_, message)) = line.split_once("// expect runtime error: ") {
+            expected.exit_code = Some(70);
+            let message: String = format!("runtime error: {}", message);
+            expected.errors.push((message, index + 1));
+        } else if let Some((_, message)) = line.split_once("// expect error: ") {
+            expected.exit_code = Some(65);
+            expected
+                .errors
+                .push((format!("error: {}", message), index + 1));
+        }
+    }
+    expected
+}
+
+// Diagnostics start with the message, followed by the location
+fn reported(stderr: &str, message: &str, location: &str) -> bool {
+    let lines: Vec<&str> = stderr.lines().collect();
+    lines
+        .windows(2)
+        .any(|pair| pair[0] == message && pair[1].contains(location))
+}
+
+fn corpus() -> Vec<PathBuf> {
+    let dir: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut scripts: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+// Runs the whole corpus with the given flags, returning a description of
+// each script that didn't behave as expected
+fn run_corpus(flags: &[&str]) -> Vec<String> {
+    let mut failures: Vec<String> = Vec::new();
+    for script in corpus() {
+        let source: String = fs::read_to_string(&script).unwrap();
+        let expected: Expectation = expectation(&source);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg("--plain")
+            .args(flags)
+            .arg(&script)
+            .output()
+            .unwrap();
+        let stdout: String = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        let printed: Vec<&str> = stdout.lines().collect();
+
+        let name: String = script.file_name().unwrap().to_string_lossy().into_owned();
+        if printed != expected.output {
+            failures.push(format!(
+                "{}: printed {:?}, expected {:?}",
+                name, printed, expected.output
+            ));
+        }
+        match expected.exit_code {
+            Some(code) => {
+                if output.status.code() != Some(code) {
+                    failures.push(format!(
+                        "{}: expected exit {}, got {:?} and stderr:\n{}",
+                        name,
+                        code,
+                        output.status.code(),
+                        stderr
+                    ));
+                }
+                for (message, line) in &expected.errors {
+                    let location: String = format!("{}:{}:", name, line);
+                    if !reported(&stderr, message, &location) {
+                        failures.push(format!(
+                            "{}: expected '{}' at line {}, got stderr:\n{}",
+                            name, message, line, stderr
+                        ));
+                    }
+                }
+            }
+            None => {
+                if !output.status.success() {
+                    failures.push(format!("{}: failed with stderr:\n{}", name, stderr));
+                }
+            }
+        }
+    }
+    failures
+}
+
+#[test]
+fn corpus_passes_on_tree_walker() {
+    let failures: Vec<String> = run_corpus(&[]);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn corpus_passes_on_vm() {
+    let failures: Vec<String> = run_corpus(&["--vm"]);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn corpus_passes_on_vm_with_gc_stress() {
+    let failures: Vec<String> = run_corpus(&["--vm", "--gc-stress"]);
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
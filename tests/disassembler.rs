@@ -0,0 +1,75 @@
+use std::{env, fs, process::Command};
+
+#[test]
+fn lists_offsets_opcodes_constants_and_source_lines() {
+    let listing: String = rlox::disassemble("var a = 1.5;\nprint a + 2;\n").unwrap();
+    let lines: Vec<&str> = listing.lines().collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            "== <script> ==",
+            "-- 1 | var a = 1.5;",
+            "0000    1 Constant            0 '1.5'",
+            "0003    | DefineGlobal        1 'a'",
+            "-- 2 | print a + 2;",
+            "0006    2 GetGlobal           1 'a'",
+            "0009    | Constant            2 '2'",
+            "0012    | Add",
+            "0013    | Print",
+            "0014    | Nil",
+            "0015    | Return",
+        ]
+    );
+}
+
+#[test]
+fn lists_nested_functions_with_jumps_and_upvalues() {
+    let listing: String = rlox::disassemble(
+        "fun outer(x) {\n  fun inner() { return x; }\n  if (x) return inner;\n}\n",
+    )
+    .unwrap();
+
+    assert!(listing.contains("== outer =="), "{}", listing);
+    assert!(listing.contains("== inner =="), "{}", listing);
+    assert!(
+        listing.contains("Closure             0 <fn inner>"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("local 1"), "{}", listing);
+    assert!(listing.contains("JumpIfFalse"), "{}", listing);
+    assert!(listing.contains("GetUpvalue          0"), "{}", listing);
+}
+
+#[test]
+fn disassembly_reports_syntax_errors() {
+    let errors = rlox::disassemble("print ;").unwrap_err();
+    assert_eq!(errors[0].message(), "Expect expression.");
+}
+
+#[test]
+fn trace_prints_stack_before_each_instruction() {
+    let path = env::temp_dir().join("rlox_trace.lox");
+    fs::write(&path, "print 1 + 2;\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--trace")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    // Program output stays on stdout, the trace goes to stderr
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    let trace: String = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines[0], "          [ <script> ]");
+    assert_eq!(lines[1], "0000    1 Constant            0 '1'");
+    assert!(
+        lines.contains(&"          [ <script> ][ 1 ][ 2 ]"),
+        "{}",
+        trace
+    );
+    assert!(lines.contains(&"          [ <script> ][ 3 ]"), "{}", trace);
+}
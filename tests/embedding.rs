@@ -0,0 +1,130 @@
+use std::{cell::Cell, env, fs, rc::Rc};
+
+use rlox::{Lox, LoxError, Value};
+
+#[test]
+fn eval_returns_value_of_final_expression() {
+    let mut lox = Lox::with_captured_output();
+    let value = lox.eval("var a = 2; a * 21;").unwrap();
+    assert_eq!(value, Value::Number(42.0));
+
+    let value = lox.eval("var b = 1;").unwrap();
+    assert_eq!(value, Value::Nil);
+}
+
+#[test]
+fn globals_persist_between_evals() {
+    let mut lox = Lox::with_captured_output();
+    lox.eval("fun double(n) { return n * 2; }").unwrap();
+    lox.set_global("limit", Value::Number(10.0));
+
+    let value = lox.eval("double(limit) > 15;").unwrap();
+    assert_eq!(value, Value::Bool(true));
+
+    lox.eval("var status = \"ok\";").unwrap();
+    assert_eq!(lox.get_global("status"), Some(Value::from("ok")));
+    assert_eq!(lox.get_global("missing"), None);
+}
+
+#[test]
+fn print_is_captured() {
+    let mut lox = Lox::with_captured_output();
+    lox.eval("print 1; print \"two\";").unwrap();
+    assert_eq!(lox.take_output(), "1\ntwo\n");
+    assert_eq!(lox.take_output(), "");
+}
+
+#[test]
+fn errors_come_back_instead_of_exiting() {
+    let mut lox = Lox::with_captured_output();
+
+    let error = lox.eval("print 1 +;").unwrap_err();
+    assert!(matches!(error, LoxError::Parse { .. }));
+    assert_eq!(error.message(), "Expect expression.");
+
+    let error = lox.eval("print undefined;").unwrap_err();
+    assert!(error.is_runtime());
+
+    let errors = lox.run("var a = ; var b = ;").unwrap_err();
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn run_file_reads_and_evaluates_script() {
+    let path = env::temp_dir().join("rlox_embedding_script.lox");
+    fs::write(&path, "var answer = 42; answer;").unwrap();
+
+    let mut lox = Lox::with_captured_output();
+    assert_eq!(lox.run_file(&path).unwrap(), Value::Number(42.0));
+    assert!(lox
+        .run_file(env::temp_dir().join("rlox_missing.lox"))
+        .is_err());
+}
+
+#[test]
+fn native_functions_are_callable_from_lox() {
+    let mut lox = Lox::with_captured_output();
+    lox.define_native("http_status", 1, |args| {
+        let code: f64 = args.get(0)?;
+        Ok(if code < 400.0 { "ok" } else { "error" }.into())
+    });
+    lox.define_native("greet", 2, |args| {
+        let name: String = args.get(0)?;
+        let excited: bool = args.get(1)?;
+        let end: &str = if excited { "!" } else { "." };
+        Ok(Value::from(format!("Hello, {}{}", name, end)))
+    });
+
+    assert_eq!(lox.eval("http_status(503);").unwrap(), Value::from("error"));
+    lox.eval("print greet(\"Lox\", true);").unwrap();
+    assert_eq!(lox.take_output(), "Hello, Lox!\n");
+}
+
+#[test]
+fn native_errors_become_runtime_errors() {
+    let mut lox = Lox::with_captured_output();
+    lox.define_native("sqrt", 1, |args| {
+        let n: f64 = args.get(0)?;
+        if n < 0.0 {
+            return Err("Can't take the root of a negative number.".to_string());
+        }
+        Ok(n.sqrt().into())
+    });
+
+    assert_eq!(lox.eval("sqrt(16);").unwrap(), Value::Number(4.0));
+
+    let error = lox.eval("sqrt(\"four\");").unwrap_err();
+    assert!(error.is_runtime());
+    assert_eq!(error.message(), "Argument 1 must be a number.");
+
+    let error = lox.eval("sqrt(-1);").unwrap_err();
+    assert_eq!(error.message(), "Can't take the root of a negative number.");
+
+    let error = lox.eval("sqrt(1, 2);").unwrap_err();
+    assert_eq!(error.message(), "Expected 1 arguments but got 2.");
+}
+
+#[test]
+fn native_closures_keep_host_state() {
+    let calls: Rc<Cell<u32>> = Rc::new(Cell::new(0));
+    let counter: Rc<Cell<u32>> = Rc::clone(&calls);
+
+    let mut lox = Lox::with_captured_output();
+    lox.define_native("tick", 0, move |_| {
+        counter.set(counter.get() + 1);
+        Ok(Value::Nil)
+    });
+    lox.eval("for (var i = 0; i < 3; i = i + 1) tick();")
+        .unwrap();
+    assert_eq!(calls.get(), 3);
+}
+
+#[test]
+fn clock_is_builtin() {
+    let mut lox = Lox::with_captured_output();
+    let value = lox.eval("clock();").unwrap();
+    assert!(matches!(value, Value::Number(seconds) if seconds > 0.0));
+
+    lox.eval("print clock;").unwrap();
+    assert_eq!(lox.take_output(), "<native fn>\n");
+}
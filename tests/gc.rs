@@ -0,0 +1,195 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+use rlox::{GcStats, Value, Vm};
+
+// Collects what the VM prints so tests can check it
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// A VM with `str(n)` for building distinct strings in a loop
+fn vm_with_str() -> Vm {
+    let mut vm: Vm = Vm::new();
+    vm.define_native("str", 1, |args| {
+        Ok(Value::from(args.get::<f64>(0)?.to_string()))
+    });
+    vm
+}
+
+fn run(vm: &mut Vm, source: &str) {
+    if let Err(errors) = vm.run(source) {
+        panic!("{}", errors[0]);
+    }
+}
+
+// Builds lots of garbage that reference counting could never free: instances
+// pointing at themselves and closures capturing their own variable.
+const CYCLES: &str = r#"
+    class Node {}
+    for (var i = 0; i < 20000; i = i + 1) {
+        var node = Node();
+        node.self = node;
+        node.label = "node " + str(i);
+
+        var recurse;
+        fun f() { return recurse; }
+        recurse = f;
+    }
+"#;
+
+#[test]
+fn collects_reference_cycles() {
+    let mut vm: Vm = vm_with_str();
+    run(&mut vm, CYCLES);
+
+    let stats: GcStats = vm.gc_stats();
+    assert!(stats.collections > 0, "{:?}", stats);
+    // Far fewer than the 100000 or so objects the loop allocated
+    assert!(stats.objects < 40000, "{:?}", stats);
+}
+
+// Keeps a few MB alive while churning through garbage, so the threshold
+// is set by the growth factor rather than the initial one
+const LIVE_AND_GARBAGE: &str = r#"
+    class Node {}
+    var list = nil;
+    for (var i = 0; i < 30000; i = i + 1) {
+        var node = Node();
+        node.next = list;
+        node.label = "kept " + str(i);
+        list = node;
+    }
+    for (var i = 0; i < 30000; i = i + 1) {
+        var garbage = "dropped " + str(i);
+    }
+"#;
+
+#[test]
+fn larger_growth_factor_collects_less_often() {
+    let mut eager: Vm = vm_with_str();
+    eager.set_gc_growth_factor(1.5);
+    run(&mut eager, LIVE_AND_GARBAGE);
+
+    let mut lazy: Vm = vm_with_str();
+    lazy.set_gc_growth_factor(8.0);
+    run(&mut lazy, LIVE_AND_GARBAGE);
+
+    assert!(
+        lazy.gc_stats().collections < eager.gc_stats().collections,
+        "{:?} vs {:?}",
+        lazy.gc_stats(),
+        eager.gc_stats()
+    );
+}
+
+#[test]
+fn stress_mode_keeps_everything_reachable_alive() {
+    let printed: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+    let mut vm: Vm = Vm::with_output(Box::new(Shared(Rc::clone(&printed))));
+    vm.set_gc_stress(true);
+    run(
+        &mut vm,
+        r#"
+        class Pair {
+            init(left, right) { this.left = left; this.right = right; }
+            sum() { return this.left + this.right; }
+        }
+        fun adder(n) {
+            fun add(x) { return x + n; }
+            return add;
+        }
+        var pairs = nil;
+        for (var i = 0; i < 50; i = i + 1) {
+            pairs = Pair("a" + "b", pairs);
+        }
+        var add2 = adder(2);
+        print add2(40);
+        print Pair(1, 2).sum();
+        print pairs.left + "c";
+        var method = Pair("x", "y").sum;
+        print method();
+        "#,
+    );
+
+    assert!(vm.gc_stats().collections > 100);
+    assert_eq!(
+        String::from_utf8(printed.borrow().clone()).unwrap(),
+        "42\n3\nabc\nxy\n"
+    );
+}
+
+#[test]
+fn unreachable_strings_leave_the_intern_table() {
+    let mut vm: Vm = vm_with_str();
+    run(
+        &mut vm,
+        r#"
+        var keep = "kept";
+        for (var i = 0; i < 50000; i = i + 1) {
+            var garbage = "string number " + str(i);
+        }
+        "#,
+    );
+    assert!(vm.gc_stats().objects < 50000, "{:?}", vm.gc_stats());
+
+    // Interned strings that survived still compare equal to new ones
+    let printed: Rc<RefCell<Vec<u8>>> = Rc::new(RefCell::new(Vec::new()));
+    let mut vm: Vm = Vm::with_output(Box::new(Shared(Rc::clone(&printed))));
+    vm.set_gc_stress(true);
+    run(&mut vm, "var keep = \"ke\" + \"pt\";");
+    run(&mut vm, "print keep == \"kept\";");
+    assert_eq!(
+        String::from_utf8(printed.borrow().clone()).unwrap(),
+        "true\n"
+    );
+}
+
+// The collected backend can be embedded like `Lox`
+#[test]
+fn vm_evaluates_and_exposes_globals() {
+    let mut vm: Vm = vm_with_str();
+    run(&mut vm, CYCLES);
+    assert_eq!(vm.eval("1 + 2;").unwrap(), Value::Number(3.0));
+    assert_eq!(
+        vm.eval("var n = 4; \"n is \" + str(n);").unwrap(),
+        Value::from("n is 4")
+    );
+    assert_eq!(vm.get_global("n"), Some(Value::Number(4.0)));
+    assert_eq!(vm.get_global("missing"), None);
+
+    // Heap objects other than strings stay inside the VM
+    assert_eq!(vm.eval("var m = 1;").unwrap(), Value::Nil);
+    let error = vm.eval("Node();").unwrap_err();
+    assert_eq!(
+        error.message(),
+        "Only numbers, strings, booleans and nil can be returned to the host."
+    );
+    assert_eq!(vm.get_global("Node"), None);
+}
+
+// A failed run unwinds the stack, closures it stored in globals must not
+// point into it afterwards
+#[test]
+fn captured_variables_survive_a_failed_run() {
+    let mut vm: Vm = Vm::new();
+    run(
+        &mut vm,
+        "var h; fun f() { var x = \"captured\"; fun g() { return x; } h = g; nope(); }",
+    );
+    let error = vm.eval("f();").unwrap_err();
+    assert_eq!(error.message(), "Undefined variable 'nope'.");
+    assert_eq!(vm.eval("h();").unwrap(), Value::from("captured"));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::run;
+
+const KEYWORDS: [&str; 16] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
+
+// Declares and prints a variable for every name, so each one has to come out
+// of the scanner as a single identifier.
+fn assert_identifiers(identifiers: &[String]) {
+    let mut source: String = String::new();
+    for identifier in identifiers {
+        source.push_str(&format!("var {} = \"{}\";\n", identifier, identifier));
+        source.push_str(&format!("print {};\n", identifier));
+    }
+
+    assert_eq!(run(&source), identifiers);
+}
+
+#[test]
+fn keyword_followed_by_letters_is_an_identifier() {
+    let identifiers: Vec<String> = KEYWORDS.iter().map(|k| format!("{}x", k)).collect();
+    assert_identifiers(&identifiers);
+}
+
+#[test]
+fn keyword_followed_by_digit_is_an_identifier() {
+    let identifiers: Vec<String> = KEYWORDS.iter().map(|k| format!("{}1", k)).collect();
+    assert_identifiers(&identifiers);
+}
+
+#[test]
+fn keyword_followed_by_underscore_is_an_identifier() {
+    let identifiers: Vec<String> = KEYWORDS.iter().map(|k| format!("{}_", k)).collect();
+    assert_identifiers(&identifiers);
+}
+
+#[test]
+fn keyword_inside_identifier_is_an_identifier() {
+    let identifiers: Vec<String> = KEYWORDS.iter().map(|k| format!("my{}", k)).collect();
+    assert_identifiers(&identifiers);
+}
+
+#[test]
+fn words_starting_with_o_are_identifiers() {
+    let identifiers: Vec<String> = ["o", "one", "order", "orr", "or_else", "oregano"]
+        .iter()
+        .map(|i| i.to_string())
+        .collect();
+    assert_identifiers(&identifiers);
+}
+
+#[test]
+fn keywords_still_scan_as_keywords() {
+    let printed: Vec<String> = run(r#"
+        print nil or "or";
+        print true and "and";
+        var orange = false;
+        print orange or "orange";
+        "#);
+    assert_eq!(printed, vec!["or", "and", "orange"]);
+}
+
+#[test]
+fn string_escapes_are_replaced() {
+    let printed: Vec<String> = run(r#"
+        print "say \"hi\"";
+        print "a\tb";
+        print "back\\slash";
+        print "two\nlines";
+        print "\u{e9}\u{1F600}";
+        "#);
+    assert_eq!(
+        printed,
+        vec!["say \"hi\"", "a\tb", "back\\slash", "two", "lines", "é😀"]
+    );
+}
+
+#[test]
+fn block_comments_nest_and_span_lines() {
+    let printed: Vec<String> = run(r#"
+        /* outer /* inner
+           still inner */ still outer */
+        print 1; /* between */ print 2;
+        print 4 /* in an expression */ / 2;
+        "#);
+    assert_eq!(printed, vec!["1", "2", "2"]);
+}
@@ -0,0 +1,81 @@
+use std::{rc::Rc, thread};
+
+use rlox::{Lox, Symbol, Value};
+
+#[test]
+fn same_text_interns_to_same_symbol() {
+    let first: Symbol = Symbol::intern("counter");
+    let built: String = format!("{}{}", "count", "er");
+    let second: Symbol = Symbol::intern(&built);
+    assert_eq!(first, second);
+    assert_ne!(first, Symbol::intern("Counter"));
+    assert_eq!(first.as_str(), "counter");
+    assert_eq!(first.to_string(), "counter");
+}
+
+#[test]
+fn symbols_are_shared_across_threads() {
+    let here: Symbol = Symbol::intern("shared between threads");
+    let there: Symbol = thread::spawn(|| Symbol::intern("shared between threads"))
+        .join()
+        .unwrap();
+    assert_eq!(here, there);
+}
+
+// Names coming from the host and from the script are interned by the same
+// table, so globals set from Rust are found by Lox code and vice versa.
+#[test]
+fn host_and_script_names_meet() {
+    let mut lox = Lox::with_captured_output();
+    lox.set_global("fromHost", Value::Number(20.0));
+    lox.eval("var fromScript = fromHost + 1;").unwrap();
+    assert_eq!(lox.get_global("fromScript"), Some(Value::Number(21.0)));
+
+    lox.eval("var text = \"a literal\";").unwrap();
+    assert_eq!(lox.get_global("text"), Some(Value::from("a literal")));
+}
+
+#[test]
+fn preset_names_are_the_interned_ones() {
+    assert_eq!(Symbol::init(), Symbol::intern("init"));
+    assert_eq!(Symbol::this(), Symbol::intern("this"));
+    assert_eq!(Symbol::super_(), Symbol::intern("super"));
+}
+
+// String and number literals aren't interned, errors still quote them
+#[test]
+fn literal_tokens_keep_their_text() {
+    let mut lox = Lox::with_captured_output();
+    let errors = lox.run("var 12.5 = 1; var \"text\" = 2;").unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(
+        errors[0].to_string().contains("Error at '12.5'"),
+        "{}",
+        errors[0]
+    );
+    assert!(
+        errors[1].to_string().contains("Error at '\"text\"'"),
+        "{}",
+        errors[1]
+    );
+}
+
+#[test]
+fn looking_up_an_unknown_global_interns_nothing() {
+    let lox = Lox::new();
+    assert_eq!(lox.get_global("neverDefinedByAnyTest"), None);
+    assert_eq!(Symbol::lookup("neverDefinedByAnyTest"), None);
+    assert_eq!(Symbol::lookup("init"), Some(Symbol::init()));
+}
+
+// Reading a string variable shares the text instead of copying it
+#[test]
+fn string_values_share_their_text() {
+    let mut lox = Lox::with_captured_output();
+    let read = lox.eval("var s = \"shared text\"; s;").unwrap();
+    let stored = lox.get_global("s").unwrap();
+    match (read, stored) {
+        (Value::Str(read), Value::Str(stored)) => assert!(Rc::ptr_eq(&read, &stored)),
+        other => panic!("{:?}", other),
+    }
+}